@@ -0,0 +1,120 @@
+//! Allocations and time per request of setting the inbound keys.
+//!
+//! Run with `cargo bench --bench intern`.
+
+use metainfo::codec::theader;
+use metainfo::policy::{InboundPolicy, Pattern};
+use metainfo::{intern, Category, ForwardWrite, MetaInfo, RPC_PREFIX_PERSISTENT};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const REQUESTS: usize = 100_000;
+
+const KEYS: [&str; 12] = [
+    "tenant", "caller", "env", "cluster", "idc", "user", "region", "zone", "stress", "canary",
+    "version", "trace",
+];
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    // warm up, which also fills the interning table
+    f();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..REQUESTS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    println!(
+        "{:<24} {:>6.1} allocs/request {:>8.0} ns/request",
+        name,
+        allocations as f64 / REQUESTS as f64,
+        elapsed.as_nanos() as f64 / REQUESTS as f64,
+    );
+}
+
+fn main() {
+    intern::set_capacity(0);
+    // the headers as they come from the wire, not `&'static str`
+    let headers: Vec<(String, String)> = KEYS
+        .iter()
+        .map(|k| {
+            (
+                format!("{}{}", RPC_PREFIX_PERSISTENT, k),
+                "value".to_owned(),
+            )
+        })
+        .collect();
+    let mut frame = Vec::new();
+    let mut mi = MetaInfo::new();
+    for (k, v) in &headers {
+        mi.strip_rpc_prefix_and_set_persistent(k.clone(), v.clone());
+    }
+    theader::encode_request(&mi, &mut frame).unwrap();
+
+    bench("to_owned keys", || {
+        let mut mi = MetaInfo::new();
+        for (k, v) in &headers {
+            let k = k.strip_prefix(RPC_PREFIX_PERSISTENT).unwrap();
+            mi.set_persistent(k.to_owned(), v.clone());
+        }
+        black_box(mi);
+    });
+
+    bench("theader, not interned", || {
+        let mut mi = MetaInfo::new();
+        theader::decode_request(&frame, &mut mi).unwrap();
+        black_box(mi);
+    });
+
+    // only the keys allowed by the inbound policy are interned on first sight
+    intern::set_capacity(intern::DEFAULT_CAPACITY);
+    let policy = Arc::new(InboundPolicy::new().allow(Category::Persistent, Pattern::glob("*")));
+    bench("theader, interned", || {
+        let mut mi = MetaInfo::new();
+        mi.set_inbound_policy(policy.clone());
+        theader::decode_request(&frame, &mut mi).unwrap();
+        black_box(mi);
+    });
+
+    intern::register(&KEYS);
+    bench("theader, registered", || {
+        let mut mi = MetaInfo::new();
+        theader::decode_request(&frame, &mut mi).unwrap();
+        black_box(mi);
+    });
+
+    // most requests carry a few keys, which are stored inline
+    let mut small = Vec::new();
+    let mut mi = MetaInfo::new();
+    for (k, v) in &headers[..3] {
+        mi.strip_rpc_prefix_and_set_persistent(k.clone(), v.clone());
+    }
+    theader::encode_request(&mi, &mut small).unwrap();
+    bench("theader, 3 keys", || {
+        let mut mi = MetaInfo::new();
+        theader::decode_request(&small, &mut mi).unwrap();
+        black_box(mi);
+    });
+}
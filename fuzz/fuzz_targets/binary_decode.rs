@@ -0,0 +1,23 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use metainfo::codec::binary::{decode_borrowed, BinaryCodec};
+use metainfo::MetaInfo;
+
+fuzz_target!(|data: &[u8]| {
+    let borrowed = decode_borrowed(data);
+    let mut mi = MetaInfo::new();
+    let decoded = BinaryCodec::new().decode(data, &mut mi);
+    assert_eq!(borrowed.is_ok(), decoded.is_ok());
+    if decoded.is_err() {
+        return;
+    }
+
+    // the upstreams and backward downstreams are not encoded again
+    let mut buf = Vec::new();
+    BinaryCodec::new().encode(&mi, &mut buf);
+    let decoded = decode_borrowed(&buf).unwrap();
+    assert!(decoded.transients().is_empty());
+    assert!(decoded.backwards().is_empty());
+    assert!(decoded.typed().is_empty());
+});
@@ -0,0 +1,201 @@
+//! Derive macros for [metainfo](https://docs.rs/metainfo).
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Error, Fields, GenericArgument, LitStr, PathArguments,
+    Type,
+};
+
+/// Derives `metainfo::MetaInfoFields` for a struct with named fields.
+///
+/// Every field is mapped to a string metainfo, and its type must implement
+/// `FromStr` and `Display`. `Option<T>` fields are optional.
+///
+/// Field attributes:
+/// - the category, one of `persistent` (the default), `transient`, `upstream`,
+///   `backward_transient`, `backward_downstream` and `string`;
+/// - `key = "..."`, the key of the metainfo, defaults to the field name;
+/// - `skip`, the field is not mapped and is filled by `Default` when reading.
+///
+/// ```ignore
+/// #[derive(MetaInfoFields)]
+/// struct CallerInfo {
+///     #[metainfo(persistent, key = "caller_svc")]
+///     service: String,
+///     cluster: Option<String>,
+/// }
+/// ```
+#[proc_macro_derive(MetaInfoFields, attributes(metainfo))]
+pub fn derive_metainfo_fields(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct Field {
+    ident: syn::Ident,
+    ty: Type,
+    category: syn::Ident,
+    key: LitStr,
+    skip: bool,
+}
+
+const CATEGORIES: [(&str, &str); 6] = [
+    ("persistent", "Persistent"),
+    ("transient", "Transient"),
+    ("upstream", "Upstream"),
+    ("backward_transient", "BackwardTransient"),
+    ("backward_downstream", "BackwardDownstream"),
+    ("string", "String"),
+];
+
+fn parse_field(field: &syn::Field) -> syn::Result<Field> {
+    let ident = field.ident.clone().expect("named field");
+    let mut category = None;
+    let mut key = None;
+    let mut skip = false;
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("metainfo")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("key") {
+                key = Some(meta.value()?.parse::<LitStr>()?);
+                return Ok(());
+            }
+            if meta.path.is_ident("skip") {
+                skip = true;
+                return Ok(());
+            }
+            for (name, variant) in CATEGORIES {
+                if meta.path.is_ident(name) {
+                    if category.is_some() {
+                        return Err(meta.error("duplicated metainfo category"));
+                    }
+                    category = Some(syn::Ident::new(variant, Span::call_site()));
+                    return Ok(());
+                }
+            }
+            Err(meta.error("unsupported metainfo attribute"))
+        })?;
+    }
+    Ok(Field {
+        key: key.unwrap_or_else(|| LitStr::new(&ident.to_string(), ident.span())),
+        category: category.unwrap_or_else(|| syn::Ident::new("Persistent", Span::call_site())),
+        ty: field.ty.clone(),
+        ident,
+        skip,
+    })
+}
+
+/// Returns `T` if the type is `Option<T>`.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let path = match ty {
+        Type::Path(ty) if ty.qself.is_none() => &ty.path,
+        _ => return None,
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &args.args[0] {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input,
+                    "MetaInfoFields only supports structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input,
+                "MetaInfoFields only supports structs with named fields",
+            ))
+        }
+    };
+    let fields = fields
+        .iter()
+        .map(parse_field)
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let mut writes = Vec::new();
+    let mut reads = Vec::new();
+    for field in fields.iter() {
+        let Field {
+            ident,
+            ty,
+            category,
+            key,
+            skip,
+        } = field;
+        if *skip {
+            reads.push(quote! { #ident: ::std::default::Default::default() });
+            continue;
+        }
+        let category = quote! { ::metainfo::Category::#category };
+        match option_inner(ty) {
+            Some(inner) => {
+                writes.push(quote! {
+                    match &self.#ident {
+                        ::std::option::Option::Some(value) => mi.set_by_category(
+                            #category,
+                            #key,
+                            ::std::string::ToString::to_string(value),
+                        ),
+                        ::std::option::Option::None => mi.del_by_category(#category, #key),
+                    }
+                });
+                reads.push(quote! {
+                    #ident: mi.get_key(&::metainfo::Key::<#inner>::new(#key, #category))?
+                });
+            }
+            None => {
+                writes.push(quote! {
+                    mi.set_by_category(
+                        #category,
+                        #key,
+                        ::std::string::ToString::to_string(&self.#ident),
+                    );
+                });
+                reads.push(quote! {
+                    #ident: mi
+                        .get_key(&::metainfo::Key::<#ty>::new(#key, #category))?
+                        .ok_or(::metainfo::FieldError::Missing {
+                            key: #key,
+                            category: #category,
+                        })?
+                });
+            }
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::metainfo::MetaInfoFields for #name #ty_generics #where_clause {
+            fn write_to(&self, mi: &mut ::metainfo::MetaInfo) {
+                #(#writes)*
+            }
+
+            fn read_from(
+                mi: &::metainfo::MetaInfo,
+            ) -> ::std::result::Result<Self, ::metainfo::FieldError> {
+                ::std::result::Result::Ok(#name {
+                    #(#reads,)*
+                })
+            }
+        }
+    })
+}
@@ -1,16 +1,73 @@
-use std::{borrow::Cow, collections::HashMap};
+#[cfg(feature = "bytes")]
+use crate::kv::BytesEntries;
+use crate::kv::Entries;
+use paste::paste;
+use std::borrow::Cow;
+use std::collections::HashMap;
 
-pub trait Backward {
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+
+/// Backward info that is passed to the upstream: both the read and the write half.
+///
+/// Implemented for every type implementing [`BackwardRead`] and [`BackwardWrite`].
+/// Its methods are those of the halves, so importing `Backward` alone doesn't
+/// make them callable; import the halves or [`prelude`](crate::prelude).
+pub trait Backward: BackwardRead + BackwardWrite {}
+
+impl<T: BackwardRead + BackwardWrite + ?Sized> Backward for T {}
+
+/// The read half of [`Backward`].
+pub trait BackwardRead {
     // We don't think backward persistent makes sense.
     fn get_backward_transient<K: AsRef<str>>(&self, key: K) -> Option<&str>;
     fn get_backward_downstream<K: AsRef<str>>(&self, key: K) -> Option<&str>;
 
-    fn get_all_backward_transients(&self)
-        -> Option<&HashMap<Cow<'static, str>, Cow<'static, str>>>;
+    /// Iterates the backward transients; `len` and `is_empty` count them.
+    fn backward_transients(&self) -> Entries<'_>;
+    /// Iterates the backward downstreams; `len` and `is_empty` count them.
+    fn backward_downstreams(&self) -> Entries<'_>;
+
+    #[cfg(feature = "bytes")]
+    fn get_backward_transient_bytes<K: AsRef<str>>(&self, key: K) -> Option<&Bytes>;
+    #[cfg(feature = "bytes")]
+    fn get_backward_downstream_bytes<K: AsRef<str>>(&self, key: K) -> Option<&Bytes>;
+
+    /// Iterates the bytes values of the backward transients.
+    #[cfg(feature = "bytes")]
+    fn backward_transient_bytes(&self) -> BytesEntries<'_>;
+    /// Iterates the bytes values of the backward downstreams.
+    #[cfg(feature = "bytes")]
+    fn backward_downstream_bytes(&self) -> BytesEntries<'_>;
+
+    // the std maps built on each call, for the code written before the iterators
+    #[deprecated(note = "use `backward_transients`, which doesn't expose the storage")]
+    fn get_all_backward_transients(&self) -> Option<HashMap<Cow<'static, str>, Cow<'static, str>>> {
+        self.backward_transients().into_hash_map()
+    }
+
+    #[deprecated(note = "use `backward_downstreams`, which doesn't expose the storage")]
     fn get_all_backward_downstreams(
         &self,
-    ) -> Option<&HashMap<Cow<'static, str>, Cow<'static, str>>>;
+    ) -> Option<HashMap<Cow<'static, str>, Cow<'static, str>>> {
+        self.backward_downstreams().into_hash_map()
+    }
 
+    #[cfg(feature = "bytes")]
+    #[deprecated(note = "use `backward_transient_bytes`, which doesn't expose the storage")]
+    fn get_all_backward_transient_bytes(&self) -> Option<HashMap<Cow<'static, str>, Bytes>> {
+        self.backward_transient_bytes().into_hash_map()
+    }
+
+    #[cfg(feature = "bytes")]
+    #[deprecated(note = "use `backward_downstream_bytes`, which doesn't expose the storage")]
+    fn get_all_backward_downstream_bytes(&self) -> Option<HashMap<Cow<'static, str>, Bytes>> {
+        self.backward_downstream_bytes().into_hash_map()
+    }
+}
+
+/// The write half of [`Backward`].
+pub trait BackwardWrite {
     fn set_backward_transient<K: Into<Cow<'static, str>>, V: Into<Cow<'static, str>>>(
         &mut self,
         key: K,
@@ -29,7 +86,12 @@ pub trait Backward {
         &mut self,
         key: K,
         value: V,
-    );
+    ) {
+        let key: Cow<'static, str> = key.into();
+        if let Some(key) = key.strip_prefix(crate::RPC_PREFIX_BACKWARD) {
+            self.set_backward_downstream(crate::intern::lookup(key), value);
+        }
+    }
 
     fn strip_http_prefix_and_set_backward_downstream<
         K: Into<Cow<'static, str>>,
@@ -38,8 +100,192 @@ pub trait Backward {
         &mut self,
         key: K,
         value: V,
-    );
+    ) {
+        let key: Cow<'static, str> = key.into();
+        if let Some(key) = key.strip_prefix(crate::HTTP_PREFIX_BACKWARD) {
+            self.set_backward_downstream(crate::intern::lookup(key), value);
+        }
+    }
 
     fn del_backward_transient<K: AsRef<str>>(&mut self, key: K);
     fn del_backward_downstream<K: AsRef<str>>(&mut self, key: K);
+
+    #[cfg(feature = "bytes")]
+    fn set_backward_transient_bytes<K: Into<Cow<'static, str>>, V: Into<Bytes>>(
+        &mut self,
+        key: K,
+        value: V,
+    );
+    #[cfg(feature = "bytes")]
+    fn set_backward_downstream_bytes<K: Into<Cow<'static, str>>, V: Into<Bytes>>(
+        &mut self,
+        key: K,
+        value: V,
+    );
+
+    #[cfg(feature = "bytes")]
+    fn del_backward_transient_bytes<K: AsRef<str>>(&mut self, key: K);
+    #[cfg(feature = "bytes")]
+    fn del_backward_downstream_bytes<K: AsRef<str>>(&mut self, key: K);
+}
+
+/// The object-safe version of [`Backward`], implemented for every `Backward`.
+///
+/// The methods are those of `Backward` prefixed by `dyn_`, so both traits can be
+/// in scope together.
+pub trait DynBackward {
+    fn dyn_get_backward_transient(&self, key: &str) -> Option<&str>;
+    fn dyn_get_backward_downstream(&self, key: &str) -> Option<&str>;
+
+    fn dyn_backward_transients(&self) -> Entries<'_>;
+    fn dyn_backward_downstreams(&self) -> Entries<'_>;
+
+    fn dyn_set_backward_transient(&mut self, key: Cow<'static, str>, value: Cow<'static, str>);
+    fn dyn_set_backward_downstream(&mut self, key: Cow<'static, str>, value: Cow<'static, str>);
+
+    fn dyn_strip_rpc_prefix_and_set_backward_downstream(
+        &mut self,
+        key: Cow<'static, str>,
+        value: Cow<'static, str>,
+    );
+    fn dyn_strip_http_prefix_and_set_backward_downstream(
+        &mut self,
+        key: Cow<'static, str>,
+        value: Cow<'static, str>,
+    );
+
+    fn dyn_del_backward_transient(&mut self, key: &str);
+    fn dyn_del_backward_downstream(&mut self, key: &str);
+
+    #[cfg(feature = "bytes")]
+    fn dyn_get_backward_transient_bytes(&self, key: &str) -> Option<&Bytes>;
+    #[cfg(feature = "bytes")]
+    fn dyn_get_backward_downstream_bytes(&self, key: &str) -> Option<&Bytes>;
+
+    #[cfg(feature = "bytes")]
+    fn dyn_backward_transient_bytes(&self) -> BytesEntries<'_>;
+    #[cfg(feature = "bytes")]
+    fn dyn_backward_downstream_bytes(&self) -> BytesEntries<'_>;
+
+    #[cfg(feature = "bytes")]
+    fn dyn_set_backward_transient_bytes(&mut self, key: Cow<'static, str>, value: Bytes);
+    #[cfg(feature = "bytes")]
+    fn dyn_set_backward_downstream_bytes(&mut self, key: Cow<'static, str>, value: Bytes);
+
+    #[cfg(feature = "bytes")]
+    fn dyn_del_backward_transient_bytes(&mut self, key: &str);
+    #[cfg(feature = "bytes")]
+    fn dyn_del_backward_downstream_bytes(&mut self, key: &str);
+}
+
+macro_rules! dyn_impl {
+    ($($name:ident),*) => {
+        paste! {
+            $(
+                #[inline]
+                fn [<dyn_get_ $name>](&self, key: &str) -> Option<&str> {
+                    BackwardRead::[<get_ $name>](self, key)
+                }
+
+                #[inline]
+                fn [<dyn_ $name s>](&self) -> Entries<'_> {
+                    BackwardRead::[<$name s>](self)
+                }
+
+                #[inline]
+                fn [<dyn_set_ $name>](&mut self, key: Cow<'static, str>, value: Cow<'static, str>) {
+                    BackwardWrite::[<set_ $name>](self, key, value)
+                }
+
+                #[inline]
+                fn [<dyn_del_ $name>](&mut self, key: &str) {
+                    BackwardWrite::[<del_ $name>](self, key)
+                }
+
+                #[cfg(feature = "bytes")]
+                #[inline]
+                fn [<dyn_get_ $name _bytes>](&self, key: &str) -> Option<&Bytes> {
+                    BackwardRead::[<get_ $name _bytes>](self, key)
+                }
+
+                #[cfg(feature = "bytes")]
+                #[inline]
+                fn [<dyn_ $name _bytes>](&self) -> BytesEntries<'_> {
+                    BackwardRead::[<$name _bytes>](self)
+                }
+
+                #[cfg(feature = "bytes")]
+                #[inline]
+                fn [<dyn_set_ $name _bytes>](&mut self, key: Cow<'static, str>, value: Bytes) {
+                    BackwardWrite::[<set_ $name _bytes>](self, key, value)
+                }
+
+                #[cfg(feature = "bytes")]
+                #[inline]
+                fn [<dyn_del_ $name _bytes>](&mut self, key: &str) {
+                    BackwardWrite::[<del_ $name _bytes>](self, key)
+                }
+            )*
+        }
+    };
+}
+
+impl<T: Backward> DynBackward for T {
+    dyn_impl!(backward_transient, backward_downstream);
+
+    #[inline]
+    fn dyn_strip_rpc_prefix_and_set_backward_downstream(
+        &mut self,
+        key: Cow<'static, str>,
+        value: Cow<'static, str>,
+    ) {
+        BackwardWrite::strip_rpc_prefix_and_set_backward_downstream(self, key, value)
+    }
+
+    #[inline]
+    fn dyn_strip_http_prefix_and_set_backward_downstream(
+        &mut self,
+        key: Cow<'static, str>,
+        value: Cow<'static, str>,
+    ) {
+        BackwardWrite::strip_http_prefix_and_set_backward_downstream(self, key, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kv::Node;
+    use crate::MetaInfo;
+
+    #[test]
+    fn test_dyn_backward() {
+        let mut carriers: Vec<Box<dyn DynBackward + Send>> =
+            vec![Box::new(MetaInfo::new()), Box::new(Node::default())];
+        for carrier in carriers.iter_mut() {
+            carrier.dyn_strip_rpc_prefix_and_set_backward_downstream(
+                "RPC_BACKWARD_cost".into(),
+                "3".into(),
+            );
+            carrier.dyn_set_backward_transient("k".into(), "v".into());
+            assert_eq!(carrier.dyn_get_backward_downstream("cost"), Some("3"));
+            carrier.dyn_del_backward_transient("k");
+            assert_eq!(carrier.dyn_get_backward_transient("k"), None);
+        }
+    }
+
+    #[test]
+    fn test_with_prelude() {
+        use crate::forward::DynForward;
+        use crate::prelude::*;
+
+        // the methods of the dyn traits don't shadow those of the prelude
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("tenant", "t1");
+        mi.set_backward_transient("cost", "3");
+        assert_eq!(mi.get_persistent("tenant"), Some("t1"));
+        assert_eq!(mi.get_backward_transient("cost"), Some("3"));
+        assert_eq!(mi.dyn_get_persistent("tenant"), Some("t1"));
+        assert_eq!(mi.dyn_get_backward_transient("cost"), Some("3"));
+    }
 }
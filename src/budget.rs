@@ -0,0 +1,286 @@
+//! Hop and time budgets of persistents and transients.
+//!
+//! A [`Budget`] limits how far an entry travels: the number of hops it's sent
+//! for, and the wall-clock time after which it's dropped. The codecs skip the
+//! entries whose hops have run out or which have expired, and send the others
+//! with one hop less. Setting or deleting a value drops its budget.
+//!
+//! Budgets apply to the text values.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::{Budget, ForwardRead, MetaInfo};
+//!
+//! let mut mi = MetaInfo::new();
+//! mi.set_persistent_with_budget("debug", "1", Budget::hops(3));
+//! assert_eq!(mi.get_persistent("debug"), Some("1"));
+//! assert_eq!(mi.get_persistent_budget("debug").unwrap().remaining_hops(), Some(3));
+//! ```
+
+use crate::{Category, MetaInfo};
+use std::borrow::Cow;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The hop and time budget of an entry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Budget {
+    hops: Option<u32>,
+    expires_at: Option<SystemTime>,
+}
+
+impl Budget {
+    /// The entry is sent for `hops` more hops.
+    #[inline]
+    pub fn hops(hops: u32) -> Self {
+        Budget {
+            hops: Some(hops),
+            expires_at: None,
+        }
+    }
+
+    /// The entry is dropped after the time.
+    #[inline]
+    pub fn expires_at(expires_at: SystemTime) -> Self {
+        Budget {
+            hops: None,
+            expires_at: Some(expires_at),
+        }
+    }
+
+    /// The entry is dropped after the ttl from now.
+    #[inline]
+    pub fn ttl(ttl: Duration) -> Self {
+        Self::expires_at(SystemTime::now() + ttl)
+    }
+
+    #[inline]
+    pub fn with_hops(mut self, hops: u32) -> Self {
+        self.hops = Some(hops);
+        self
+    }
+
+    #[inline]
+    pub fn with_expiry(mut self, expires_at: SystemTime) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    #[inline]
+    pub fn remaining_hops(&self) -> Option<u32> {
+        self.hops
+    }
+
+    #[inline]
+    pub fn expiry(&self) -> Option<SystemTime> {
+        self.expires_at
+    }
+
+    #[inline]
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires_at.is_some_and(|t| t <= now)
+    }
+
+    /// Returns the budget to send the entry with, or `None` if it's not sent.
+    pub fn next_hop(&self, now: SystemTime) -> Option<Budget> {
+        if self.is_expired(now) {
+            return None;
+        }
+        let hops = match self.hops {
+            Some(0) => return None,
+            hops => hops.map(|h| h - 1),
+        };
+        Some(Budget {
+            hops,
+            expires_at: self.expires_at,
+        })
+    }
+
+    /// Returns the expiry in unix milliseconds, used on the wire.
+    pub(crate) fn expiry_millis(&self) -> Option<u64> {
+        self.expires_at.map(|t| {
+            t.duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default()
+        })
+    }
+
+    pub(crate) fn from_wire(hops: Option<u32>, expiry_millis: Option<u64>) -> Self {
+        Budget {
+            hops,
+            expires_at: expiry_millis
+                .and_then(|ms| UNIX_EPOCH.checked_add(Duration::from_millis(ms))),
+        }
+    }
+
+    /// Formats the budget as a header value, such as `hops=2;expires=1700000000000`.
+    pub(crate) fn to_header_value(self) -> String {
+        let hops = self.hops.map(|h| format!("hops={}", h));
+        let expiry = self.expiry_millis().map(|ms| format!("expires={}", ms));
+        hops.into_iter().chain(expiry).collect::<Vec<_>>().join(";")
+    }
+
+    pub(crate) fn parse_header_value(value: &str) -> Option<Self> {
+        let (mut hops, mut expiry) = (None, None);
+        for part in value.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+            match part.split_once('=')? {
+                ("hops", h) => hops = Some(h.parse().ok()?),
+                ("expires", ms) => expiry = Some(ms.parse().ok()?),
+                _ => return None,
+            }
+        }
+        Some(Self::from_wire(hops, expiry))
+    }
+}
+
+impl MetaInfo {
+    /// Set the persistent with the budget.
+    pub fn set_persistent_with_budget<K: Into<Cow<'static, str>>, V: Into<Cow<'static, str>>>(
+        &mut self,
+        key: K,
+        value: V,
+        budget: Budget,
+    ) {
+        let key = key.into();
+        self.set_by_category(Category::Persistent, key.clone(), value);
+        self.set_budget(Category::Persistent, key, budget);
+    }
+
+    /// Set the transient with the budget.
+    pub fn set_transient_with_budget<K: Into<Cow<'static, str>>, V: Into<Cow<'static, str>>>(
+        &mut self,
+        key: K,
+        value: V,
+        budget: Budget,
+    ) {
+        let key = key.into();
+        self.set_by_category(Category::Transient, key.clone(), value);
+        self.set_budget(Category::Transient, key, budget);
+    }
+
+    #[inline]
+    pub fn get_persistent_budget<K: AsRef<str>>(&self, key: K) -> Option<&Budget> {
+        self.get_budget(Category::Persistent, key.as_ref())
+    }
+
+    #[inline]
+    pub fn get_transient_budget<K: AsRef<str>>(&self, key: K) -> Option<&Budget> {
+        self.get_budget(Category::Transient, key.as_ref())
+    }
+
+    /// Get the budget of the upstream, received with it.
+    #[inline]
+    pub fn get_upstream_budget<K: AsRef<str>>(&self, key: K) -> Option<&Budget> {
+        self.get_budget(Category::Upstream, key.as_ref())
+    }
+
+    /// Returns whether the entry is sent, and the budget to send it with.
+    ///
+    /// The entry isn't sent if the outbound policy doesn't allow it.
+    pub(crate) fn outbound_budget(
+        &self,
+        category: Category,
+        key: &str,
+        now: SystemTime,
+    ) -> Option<Option<Budget>> {
+        if !self.outbound_allows(category, key) {
+            return None;
+        }
+        match self.get_budget(category, key) {
+            Some(budget) => budget.next_hop(now).map(Some),
+            None => Some(None),
+        }
+    }
+
+    /// Sets the received budget, drops the entry if it has expired.
+    pub(crate) fn set_inbound_budget(
+        &mut self,
+        category: Category,
+        key: &str,
+        budget: Budget,
+        now: SystemTime,
+    ) {
+        if self.get_by_category(category, key).is_none() {
+            return;
+        }
+        if budget.is_expired(now) {
+            self.del_by_category(category, key);
+        } else {
+            self.set_budget(category, key.to_owned().into(), budget);
+        }
+    }
+
+    fn get_budget(&self, category: Category, key: &str) -> Option<&Budget> {
+        match category {
+            Category::Persistent => self.forward_node.as_ref()?.get_persistent_budget(key),
+            Category::Transient => self.forward_node.as_ref()?.get_transient_budget(key),
+            Category::Upstream => self.forward_node.as_ref()?.get_stale_budget(key),
+            _ => None,
+        }
+    }
+
+    fn set_budget(&mut self, category: Category, key: Cow<'static, str>, budget: Budget) {
+        self.ensure_forward_node();
+        let node = self.forward_node.as_mut().unwrap();
+        match category {
+            Category::Persistent => node.set_persistent_budget(key, budget),
+            Category::Transient => node.set_transient_budget(key, budget),
+            Category::Upstream => node.set_stale_budget(key, budget),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ForwardRead, ForwardWrite};
+
+    #[test]
+    fn test_next_hop() {
+        let now = SystemTime::now();
+        let budget = Budget::hops(1).with_expiry(now + Duration::from_secs(1));
+        let next = budget.next_hop(now).unwrap();
+        assert_eq!(next.remaining_hops(), Some(0));
+        assert_eq!(next.expiry(), budget.expiry());
+        assert_eq!(next.next_hop(now), None);
+        assert_eq!(budget.next_hop(now + Duration::from_secs(1)), None);
+        assert_eq!(Budget::default().next_hop(now).unwrap(), Budget::default());
+    }
+
+    #[test]
+    fn test_header_value() {
+        let budget = Budget::from_wire(Some(2), Some(1_700_000_000_000));
+        assert_eq!(budget.to_header_value(), "hops=2;expires=1700000000000");
+        assert_eq!(
+            Budget::parse_header_value("hops=2; expires=1700000000000"),
+            Some(budget)
+        );
+        assert_eq!(Budget::parse_header_value("hops=x"), None);
+        assert_eq!(Budget::parse_header_value("ttl=1"), None);
+    }
+
+    #[test]
+    fn test_budget() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent_with_budget("k", "v", Budget::hops(0));
+        let now = SystemTime::now();
+        assert_eq!(mi.outbound_budget(Category::Persistent, "k", now), None);
+        assert_eq!(
+            mi.outbound_budget(Category::Persistent, "other", now),
+            Some(None)
+        );
+
+        // the children have the budgets
+        let (mut mi, child) = mi.derive();
+        assert_eq!(child.get_persistent_budget("k"), Some(&Budget::hops(0)));
+
+        // setting the value again drops the budget
+        mi.set_persistent("k", "v2");
+        assert_eq!(mi.get_persistent_budget("k"), None);
+
+        mi.set_upstream("u", "v");
+        let expired = Budget::expires_at(now - Duration::from_secs(1));
+        mi.set_inbound_budget(Category::Upstream, "u", expired, now);
+        assert_eq!(mi.get_upstream("u"), None);
+    }
+}
@@ -0,0 +1,40 @@
+/// The kinds of string k-v a [`MetaInfo`](crate::MetaInfo) carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// Forward info that is passed to every downstream hop.
+    Persistent,
+    /// Forward info that is only passed to the next hop.
+    Transient,
+    /// Transient info received from the upstream.
+    Upstream,
+    /// Backward info that is only passed to the previous hop.
+    BackwardTransient,
+    /// Backward transient info received from the downstream.
+    BackwardDownstream,
+    /// Process-local string k-v, never transmitted.
+    String,
+}
+
+impl Category {
+    /// All categories, in a stable order.
+    pub const ALL: [Category; 6] = [
+        Category::Persistent,
+        Category::Transient,
+        Category::Upstream,
+        Category::BackwardTransient,
+        Category::BackwardDownstream,
+        Category::String,
+    ];
+
+    /// Returns a short lowercase name, suitable for logs.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Category::Persistent => "persistent",
+            Category::Transient => "transient",
+            Category::Upstream => "upstream",
+            Category::BackwardTransient => "backward_transient",
+            Category::BackwardDownstream => "backward_downstream",
+            Category::String => "string",
+        }
+    }
+}
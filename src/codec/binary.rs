@@ -0,0 +1,708 @@
+//! A compact binary encoding of `MetaInfo` for binary RPC frames.
+//!
+//! The format is a version byte followed by sections:
+//!
+//! ```text
+//! frame   := version:u8 section*
+//! section := tag:u8 count:varint entry{count}
+//! entry   := len:varint key len:varint value
+//! ```
+//!
+//! Varints are unsigned LEB128. The sections of string k-v are tagged by
+//! [`TAG_PERSISTENT`], [`TAG_TRANSIENT`] and [`TAG_BACKWARD`]; registered
+//! typed entries are in the section tagged by [`TAG_TYPED`], whose entries are
+//! the name of the entry and its payload. With the `multi_value` feature, the
+//! several values of a key are repeated entries of the key. The bytes values are in the sections
+//! tagged by [`TAG_PERSISTENT_BYTES`], [`TAG_TRANSIENT_BYTES`] and
+//! [`TAG_BACKWARD_BYTES`], which are only written and read with the `bytes`
+//! feature, and skipped otherwise.
+//!
+//! The budgets of the persistents and transients are in the section tagged by
+//! [`TAG_BUDGET`], whose entries are the tag of the value, the key, the flags
+//! and the remaining hops and expiry in unix milliseconds, as the flags tell.
+//! The entries whose budgets have run out are not written.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::codec::binary::BinaryCodec;
+//! use metainfo::{ForwardRead, ForwardWrite, MetaInfo};
+//!
+//! let codec = BinaryCodec::new();
+//! let mut mi = MetaInfo::new();
+//! mi.set_persistent("tenant", "t1");
+//! mi.set_transient("caller", "svc");
+//!
+//! let mut buf = Vec::new();
+//! codec.encode(&mi, &mut buf);
+//!
+//! let mut received = MetaInfo::new();
+//! codec.decode(&buf, &mut received).unwrap();
+//! assert_eq!(received.get_persistent("tenant"), Some("t1"));
+//! assert_eq!(received.get_upstream("caller"), Some("svc"));
+//! ```
+
+use crate::{BackwardRead, BackwardWrite, Budget, Category, ForwardRead, ForwardWrite, MetaInfo};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::SystemTime;
+
+pub const VERSION: u8 = 1;
+
+pub const TAG_PERSISTENT: u8 = 0x01;
+pub const TAG_TRANSIENT: u8 = 0x02;
+pub const TAG_BACKWARD: u8 = 0x03;
+pub const TAG_PERSISTENT_BYTES: u8 = 0x04;
+pub const TAG_TRANSIENT_BYTES: u8 = 0x05;
+pub const TAG_BACKWARD_BYTES: u8 = 0x06;
+pub const TAG_TYPED: u8 = 0x10;
+pub const TAG_BUDGET: u8 = 0x11;
+
+const BUDGET_HOPS: u8 = 0x01;
+const BUDGET_EXPIRY: u8 = 0x02;
+
+/// A typed entry of `MetaInfo` which can be carried by [`BinaryCodec`].
+pub trait BinaryEntry: Sized + Send + Sync + 'static {
+    /// The name of the entry on the wire, must be unique among registered entries.
+    const NAME: &'static str;
+
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Returns `None` if the payload is malformed.
+    fn decode(payload: &[u8]) -> Option<Self>;
+}
+
+/// Errors of decoding binary frames.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    UnsupportedVersion(u8),
+    UnknownTag(u8),
+    UnexpectedEof,
+    InvalidVarint,
+    InvalidUtf8,
+    /// The payload of a registered typed entry is malformed.
+    InvalidEntry(&'static str),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnsupportedVersion(v) => write!(f, "unsupported version {}", v),
+            DecodeError::UnknownTag(tag) => write!(f, "unknown section tag {:#04x}", tag),
+            DecodeError::UnexpectedEof => f.write_str("unexpected end of frame"),
+            DecodeError::InvalidVarint => f.write_str("invalid varint"),
+            DecodeError::InvalidUtf8 => f.write_str("invalid utf-8 string"),
+            DecodeError::InvalidEntry(name) => write!(f, "invalid typed entry {}", name),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// A frame decoded without copying, borrowing from the input buffer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Decoded<'a> {
+    persistents: Vec<(&'a str, &'a str)>,
+    transients: Vec<(&'a str, &'a str)>,
+    backwards: Vec<(&'a str, &'a str)>,
+    persistent_bytes: Vec<(&'a str, &'a [u8])>,
+    transient_bytes: Vec<(&'a str, &'a [u8])>,
+    backward_bytes: Vec<(&'a str, &'a [u8])>,
+    typed: Vec<(&'a str, &'a [u8])>,
+    budgets: Vec<(Category, &'a str, Budget)>,
+}
+
+impl<'a> Decoded<'a> {
+    #[inline]
+    pub fn persistents(&self) -> &[(&'a str, &'a str)] {
+        &self.persistents
+    }
+
+    #[inline]
+    pub fn transients(&self) -> &[(&'a str, &'a str)] {
+        &self.transients
+    }
+
+    #[inline]
+    pub fn backwards(&self) -> &[(&'a str, &'a str)] {
+        &self.backwards
+    }
+
+    #[inline]
+    pub fn persistent_bytes(&self) -> &[(&'a str, &'a [u8])] {
+        &self.persistent_bytes
+    }
+
+    #[inline]
+    pub fn transient_bytes(&self) -> &[(&'a str, &'a [u8])] {
+        &self.transient_bytes
+    }
+
+    #[inline]
+    pub fn backward_bytes(&self) -> &[(&'a str, &'a [u8])] {
+        &self.backward_bytes
+    }
+
+    /// Returns the budgets of the persistents and transients.
+    #[inline]
+    pub fn budgets(&self) -> &[(Category, &'a str, Budget)] {
+        &self.budgets
+    }
+
+    /// Returns the names and payloads of the typed entries.
+    #[inline]
+    pub fn typed(&self) -> &[(&'a str, &'a [u8])] {
+        &self.typed
+    }
+}
+
+/// Decodes a frame without copying.
+pub fn decode_borrowed(buf: &[u8]) -> Result<Decoded<'_>, DecodeError> {
+    let mut reader = Reader { buf };
+    let version = reader.byte()?;
+    if version != VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    let mut decoded = Decoded::default();
+    while !reader.buf.is_empty() {
+        let tag = reader.byte()?;
+        let count = reader.varint()?;
+        let entries = match tag {
+            TAG_PERSISTENT => &mut decoded.persistents,
+            TAG_TRANSIENT => &mut decoded.transients,
+            TAG_BACKWARD => &mut decoded.backwards,
+            TAG_BUDGET => {
+                for _ in 0..count {
+                    let category = match reader.byte()? {
+                        TAG_PERSISTENT => Category::Persistent,
+                        TAG_TRANSIENT => Category::Transient,
+                        tag => return Err(DecodeError::UnknownTag(tag)),
+                    };
+                    let key = reader.str()?;
+                    let flags = reader.byte()?;
+                    let hops = match flags & BUDGET_HOPS {
+                        0 => None,
+                        _ => Some(
+                            u32::try_from(reader.varint()?)
+                                .map_err(|_| DecodeError::InvalidVarint)?,
+                        ),
+                    };
+                    let expiry = match flags & BUDGET_EXPIRY {
+                        0 => None,
+                        _ => Some(reader.varint()?),
+                    };
+                    decoded
+                        .budgets
+                        .push((category, key, Budget::from_wire(hops, expiry)));
+                }
+                continue;
+            }
+            tag => {
+                let entries = match tag {
+                    TAG_PERSISTENT_BYTES => &mut decoded.persistent_bytes,
+                    TAG_TRANSIENT_BYTES => &mut decoded.transient_bytes,
+                    TAG_BACKWARD_BYTES => &mut decoded.backward_bytes,
+                    TAG_TYPED => &mut decoded.typed,
+                    tag => return Err(DecodeError::UnknownTag(tag)),
+                };
+                for _ in 0..count {
+                    let key = reader.str()?;
+                    let value = reader.bytes()?;
+                    entries.push((key, value));
+                }
+                continue;
+            }
+        };
+        for _ in 0..count {
+            let key = reader.str()?;
+            let value = reader.str()?;
+            entries.push((key, value));
+        }
+    }
+    Ok(decoded)
+}
+
+struct Registered {
+    encode: fn(&MetaInfo, &mut Vec<u8>) -> bool,
+    decode: fn(&[u8], &mut MetaInfo) -> bool,
+}
+
+fn encode_entry<T: BinaryEntry>(mi: &MetaInfo, buf: &mut Vec<u8>) -> bool {
+    match mi.get::<T>() {
+        Some(entry) => {
+            entry.encode(buf);
+            true
+        }
+        None => false,
+    }
+}
+
+fn decode_entry<T: BinaryEntry>(payload: &[u8], mi: &mut MetaInfo) -> bool {
+    match T::decode(payload) {
+        Some(entry) => {
+            mi.insert(entry);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Encodes and decodes `MetaInfo` in the compact binary format.
+#[derive(Default)]
+pub struct BinaryCodec {
+    typed: HashMap<&'static str, Registered>,
+}
+
+impl BinaryCodec {
+    /// Creates a codec without typed entries.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Registers a typed entry to be carried.
+    pub fn register<T: BinaryEntry>(mut self) -> Self {
+        self.typed.insert(
+            T::NAME,
+            Registered {
+                encode: encode_entry::<T>,
+                decode: decode_entry::<T>,
+            },
+        );
+        self
+    }
+
+    /// Appends the frame of `MetaInfo` to the buffer.
+    pub fn encode(&self, mi: &MetaInfo, buf: &mut Vec<u8>) {
+        buf.push(VERSION);
+        let now = SystemTime::now();
+        let mut budgets = Vec::new();
+        for (tag, category, map) in [
+            (TAG_PERSISTENT, Category::Persistent, mi.persistents()),
+            (TAG_TRANSIENT, Category::Transient, mi.transients()),
+            (
+                TAG_BACKWARD,
+                Category::BackwardTransient,
+                mi.backward_transients(),
+            ),
+        ] {
+            let mut entries = Vec::new();
+            for (k, v) in map {
+                match mi.outbound_budget(category, k, now) {
+                    Some(Some(budget)) => budgets.push((tag, k, budget)),
+                    Some(None) => {}
+                    None => continue,
+                }
+                entries.push((k, v));
+            }
+            // the values after the first one are repeated entries of the key
+            entries.extend(
+                extras(mi, category)
+                    .filter(|(k, _)| mi.outbound_budget(category, k, now).is_some()),
+            );
+            if !entries.is_empty() {
+                buf.push(tag);
+                put_varint(buf, entries.len() as u64);
+                for (k, v) in entries {
+                    put_bytes(buf, k.as_bytes());
+                    put_bytes(buf, v.as_bytes());
+                }
+            }
+        }
+        if !budgets.is_empty() {
+            buf.push(TAG_BUDGET);
+            put_varint(buf, budgets.len() as u64);
+            for (tag, k, budget) in budgets {
+                buf.push(tag);
+                put_bytes(buf, k.as_bytes());
+                let (hops, expiry) = (budget.remaining_hops(), budget.expiry_millis());
+                buf.push(hops.map_or(0, |_| BUDGET_HOPS) | expiry.map_or(0, |_| BUDGET_EXPIRY));
+                if let Some(hops) = hops {
+                    put_varint(buf, hops as u64);
+                }
+                if let Some(expiry) = expiry {
+                    put_varint(buf, expiry);
+                }
+            }
+        }
+        #[cfg(feature = "bytes")]
+        for (tag, category) in [
+            (TAG_PERSISTENT_BYTES, Category::Persistent),
+            (TAG_TRANSIENT_BYTES, Category::Transient),
+            (TAG_BACKWARD_BYTES, Category::BackwardTransient),
+        ] {
+            let map = mi.outbound_bytes_entries(category);
+            if !map.is_empty() {
+                buf.push(tag);
+                put_varint(buf, map.len() as u64);
+                for (k, v) in map {
+                    put_bytes(buf, k.as_bytes());
+                    put_bytes(buf, v);
+                }
+            }
+        }
+
+        if self.typed.is_empty() {
+            return;
+        }
+        let mut count = 0;
+        let mut entries = Vec::new();
+        let mut payload = Vec::new();
+        for (name, registered) in self.typed.iter() {
+            payload.clear();
+            if (registered.encode)(mi, &mut payload) {
+                count += 1;
+                put_bytes(&mut entries, name.as_bytes());
+                put_bytes(&mut entries, &payload);
+            }
+        }
+        if count > 0 {
+            buf.push(TAG_TYPED);
+            put_varint(buf, count);
+            buf.extend_from_slice(&entries);
+        }
+    }
+
+    /// Decodes the frame into `MetaInfo`.
+    ///
+    /// The inbound policy of `MetaInfo` applies, and the typed entries which are
+    /// not registered are ignored. Nothing is set if it fails.
+    pub fn decode(&self, buf: &[u8], mi: &mut MetaInfo) -> Result<(), DecodeError> {
+        let decoded = decode_borrowed(buf)?;
+        // the typed entries are applied with the others, once all of them are valid
+        let mut typed = MetaInfo::new();
+        for (name, payload) in decoded.typed.iter() {
+            if let Some((&name, registered)) = self.typed.get_key_value(*name) {
+                if !(registered.decode)(payload, &mut typed) {
+                    return Err(DecodeError::InvalidEntry(name));
+                }
+            }
+        }
+        for (category, entries) in [
+            (Category::Persistent, &decoded.persistents),
+            (Category::Upstream, &decoded.transients),
+            (Category::BackwardDownstream, &decoded.backwards),
+        ] {
+            #[cfg(feature = "multi_value")]
+            let mut seen = std::collections::HashSet::new();
+            for (k, v) in entries {
+                if !mi.inbound_allows(category, k) {
+                    continue;
+                }
+                // the repeated entries of a key are its several values
+                #[cfg(feature = "multi_value")]
+                if !seen.insert(*k) {
+                    mi.append_inbound(category, k, v);
+                    continue;
+                }
+                let (k, v) = (mi.inbound_key(category, k), Cow::Owned(v.to_string()));
+                match category {
+                    Category::Persistent => mi.set_persistent(k, v),
+                    Category::Upstream => mi.set_upstream(k, v),
+                    _ => mi.set_backward_downstream(k, v),
+                }
+            }
+        }
+        #[cfg(feature = "bytes")]
+        for (category, entries) in [
+            (Category::Persistent, &decoded.persistent_bytes),
+            (Category::Upstream, &decoded.transient_bytes),
+            (Category::BackwardDownstream, &decoded.backward_bytes),
+        ] {
+            for (k, v) in entries {
+                if !mi.inbound_allows(category, k) {
+                    continue;
+                }
+                let (k, v) = (
+                    mi.inbound_key(category, k),
+                    bytes::Bytes::copy_from_slice(v),
+                );
+                match category {
+                    Category::Persistent => mi.set_persistent_bytes(k, v),
+                    Category::Upstream => mi.set_upstream_bytes(k, v),
+                    _ => mi.set_backward_downstream_bytes(k, v),
+                }
+            }
+        }
+        let now = SystemTime::now();
+        for (category, k, budget) in decoded.budgets.iter() {
+            let category = match category {
+                Category::Persistent => Category::Persistent,
+                _ => Category::Upstream,
+            };
+            mi.set_inbound_budget(category, k, *budget, now);
+        }
+        mi.extend(typed);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "multi_value")]
+fn extras(mi: &MetaInfo, category: Category) -> impl Iterator<Item = (&str, &str)> {
+    mi.extra_values(category)
+}
+
+#[cfg(not(feature = "multi_value"))]
+fn extras(_: &MetaInfo, _: Category) -> std::iter::Empty<(&str, &str)> {
+    std::iter::empty()
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        buf.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        let (b, rest) = self.buf.split_first().ok_or(DecodeError::UnexpectedEof)?;
+        self.buf = rest;
+        Ok(*b)
+    }
+
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            if shift == 63 && b > 1 {
+                return Err(DecodeError::InvalidVarint);
+            }
+            n |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(DecodeError::InvalidVarint)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], DecodeError> {
+        let len = self.varint()?;
+        if len > self.buf.len() as u64 {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        let (bytes, rest) = self.buf.split_at(len as usize);
+        self.buf = rest;
+        Ok(bytes)
+    }
+
+    fn str(&mut self) -> Result<&'a str, DecodeError> {
+        std::str::from_utf8(self.bytes()?).map_err(|_| DecodeError::InvalidUtf8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[derive(Debug, PartialEq)]
+    struct Expiry(u64);
+
+    impl BinaryEntry for Expiry {
+        const NAME: &'static str = "expiry";
+
+        fn encode(&self, buf: &mut Vec<u8>) {
+            buf.extend_from_slice(&self.0.to_be_bytes());
+        }
+
+        fn decode(payload: &[u8]) -> Option<Self> {
+            Some(Expiry(u64::from_be_bytes(payload.try_into().ok()?)))
+        }
+    }
+
+    #[test]
+    fn test_varint() {
+        for n in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut buf = Vec::new();
+            put_varint(&mut buf, n);
+            let mut reader = Reader { buf: &buf };
+            assert_eq!(reader.varint(), Ok(n));
+            assert!(reader.buf.is_empty());
+        }
+        let mut reader = Reader { buf: &[0xff; 10] };
+        assert_eq!(reader.varint(), Err(DecodeError::InvalidVarint));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let codec = BinaryCodec::new().register::<Expiry>();
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("p", "1");
+        mi.set_transient("t", "2");
+        mi.set_upstream("u", "ignored");
+        mi.set_backward_transient("b", "3");
+        mi.insert(Expiry(42));
+
+        let mut buf = Vec::new();
+        codec.encode(&mi, &mut buf);
+
+        let decoded = decode_borrowed(&buf).unwrap();
+        assert_eq!(decoded.persistents(), [("p", "1")]);
+        assert_eq!(decoded.transients(), [("t", "2")]);
+        assert_eq!(decoded.backwards(), [("b", "3")]);
+        assert_eq!(decoded.typed(), [("expiry", &42u64.to_be_bytes()[..])]);
+
+        let mut received = MetaInfo::new();
+        codec.decode(&buf, &mut received).unwrap();
+        assert_eq!(received.get_persistent("p"), Some("1"));
+        assert_eq!(received.get_upstream("t"), Some("2"));
+        assert_eq!(received.get_upstream("u"), None);
+        assert_eq!(received.get_backward_downstream("b"), Some("3"));
+        assert_eq!(received.get::<Expiry>(), Some(&Expiry(42)));
+
+        // typed entries are ignored if not registered
+        let mut received = MetaInfo::new();
+        BinaryCodec::new().decode(&buf, &mut received).unwrap();
+        assert_eq!(received.get::<Expiry>(), None);
+    }
+
+    #[cfg(feature = "multi_value")]
+    #[test]
+    fn test_multi_value() {
+        let mut mi = MetaInfo::new();
+        mi.append_persistent("hops", "a");
+        mi.append_persistent("hops", "b");
+
+        let mut buf = Vec::new();
+        BinaryCodec::new().encode(&mi, &mut buf);
+        assert_eq!(decode_borrowed(&buf).unwrap().persistents().len(), 2);
+
+        let mut received = MetaInfo::new();
+        received.set_persistent("hops", "stale");
+        BinaryCodec::new().decode(&buf, &mut received).unwrap();
+        assert_eq!(
+            received.get_all_values(Category::Persistent, "hops"),
+            ["a", "b"]
+        );
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_bytes() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent_bytes("p", vec![0xff]);
+        mi.set_backward_transient_bytes("b", vec![0, 1]);
+
+        let mut buf = Vec::new();
+        BinaryCodec::new().encode(&mi, &mut buf);
+        let decoded = decode_borrowed(&buf).unwrap();
+        assert_eq!(decoded.persistent_bytes(), [("p", &[0xff][..])]);
+        assert_eq!(decoded.backward_bytes(), [("b", &[0, 1][..])]);
+
+        let mut received = MetaInfo::new();
+        BinaryCodec::new().decode(&buf, &mut received).unwrap();
+        assert_eq!(received.get_persistent_bytes("p").unwrap()[..], [0xff]);
+        assert_eq!(
+            received.get_backward_downstream_bytes("b").unwrap()[..],
+            [0, 1]
+        );
+    }
+
+    #[test]
+    fn test_budget() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent_with_budget("p", "1", Budget::hops(2));
+        mi.set_persistent_with_budget("last", "1", Budget::hops(0));
+        mi.set_transient_with_budget("t", "2", Budget::ttl(Duration::from_secs(60)));
+
+        let mut buf = Vec::new();
+        BinaryCodec::new().encode(&mi, &mut buf);
+        let decoded = decode_borrowed(&buf).unwrap();
+        assert_eq!(decoded.persistents(), [("p", "1")]);
+        assert_eq!(decoded.budgets().len(), 2);
+
+        let mut received = MetaInfo::new();
+        BinaryCodec::new().decode(&buf, &mut received).unwrap();
+        assert_eq!(received.get_persistent("last"), None);
+        assert_eq!(
+            received
+                .get_persistent_budget("p")
+                .unwrap()
+                .remaining_hops(),
+            Some(1)
+        );
+        let budget = received.get_upstream_budget("t").unwrap();
+        assert_eq!(
+            budget.expiry_millis(),
+            mi.get_transient_budget("t").unwrap().expiry_millis()
+        );
+
+        // the expired entries are dropped on receiving
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("p", "1");
+        let mut buf = Vec::new();
+        BinaryCodec::new().encode(&mi, &mut buf);
+        buf.extend_from_slice(&[TAG_BUDGET, 1, TAG_PERSISTENT, 1, b'p', BUDGET_EXPIRY, 1]);
+        let mut received = MetaInfo::new();
+        BinaryCodec::new().decode(&buf, &mut received).unwrap();
+        assert_eq!(received.get_persistent("p"), None);
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut buf = Vec::new();
+        BinaryCodec::new().encode(&MetaInfo::new(), &mut buf);
+        assert_eq!(buf, [VERSION]);
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert_eq!(decode_borrowed(&[]), Err(DecodeError::UnexpectedEof));
+        assert_eq!(
+            decode_borrowed(&[2]),
+            Err(DecodeError::UnsupportedVersion(2))
+        );
+        assert_eq!(
+            decode_borrowed(&[VERSION, 0x7f, 0]),
+            Err(DecodeError::UnknownTag(0x7f))
+        );
+        assert_eq!(
+            decode_borrowed(&[VERSION, TAG_PERSISTENT, 1, 5, b'a']),
+            Err(DecodeError::UnexpectedEof)
+        );
+        assert_eq!(
+            decode_borrowed(&[VERSION, TAG_PERSISTENT, 1, 1, 0xff, 0]),
+            Err(DecodeError::InvalidUtf8)
+        );
+        // a huge count doesn't allocate up front
+        assert_eq!(
+            decode_borrowed(&[VERSION, TAG_TRANSIENT, 0xff, 0xff, 0xff, 0xff, 0x0f]),
+            Err(DecodeError::UnexpectedEof)
+        );
+
+        let codec = BinaryCodec::new().register::<Expiry>();
+        let buf = [
+            VERSION, TAG_TYPED, 1, 6, b'e', b'x', b'p', b'i', b'r', b'y', 1, 0,
+        ];
+        assert_eq!(
+            codec.decode(&buf, &mut MetaInfo::new()),
+            Err(DecodeError::InvalidEntry("expiry"))
+        );
+
+        // nothing is set if a later typed entry is invalid
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("k", "v");
+        mi.insert(Expiry(42));
+        let mut buf = Vec::new();
+        codec.encode(&mi, &mut buf);
+        buf.extend_from_slice(&[TAG_TYPED, 1, 6, b'e', b'x', b'p', b'i', b'r', b'y', 1, 0]);
+        let mut received = MetaInfo::new();
+        assert_eq!(
+            codec.decode(&buf, &mut received),
+            Err(DecodeError::InvalidEntry("expiry"))
+        );
+        assert_eq!(received.get::<Expiry>(), None);
+        assert_eq!(received.get_persistent("k"), None);
+    }
+}
@@ -0,0 +1,573 @@
+//! Maps `MetaInfo` onto gRPC metadata.
+//!
+//! The metainfo is carried with the `HTTP_PREFIX_*` prefixes, and the keys are
+//! lowercased. The bytes values are carried in the keys suffixed by `-bin`,
+//! whose values are base64 encoded. The [`Deadline`] is
+//! carried by `grpc-timeout`, and the budgets by the keys with the
+//! `HTTP_PREFIX_BUDGET_*` prefixes.
+//!
+//! With the `multi_value` feature, the several values of a key are repeated
+//! entries of the key, which needs a multimap such as `Vec<(String, String)>`.
+//!
+//! The codec works on any [`MetadataMap`], which is implemented for
+//! `HashMap<String, String>` and `Vec<(String, String)>`, and is easy to
+//! implement for the metadata types of gRPC frameworks.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::codec::grpc;
+//! use metainfo::{ForwardRead, ForwardWrite, MetaInfo};
+//! use std::collections::HashMap;
+//!
+//! let mut mi = MetaInfo::new();
+//! mi.set_persistent("tenant", "t1");
+//! mi.set_persistent_bytes("auth", vec![0xde, 0xad]);
+//!
+//! let mut metadata = HashMap::new();
+//! grpc::encode_request(&mi, &mut metadata).unwrap();
+//! assert_eq!(metadata["rpc-persist-tenant"], "t1");
+//! assert_eq!(metadata["rpc-persist-auth-bin"], "3q0");
+//!
+//! let mut received = MetaInfo::new();
+//! grpc::decode_request(&metadata, &mut received);
+//! assert_eq!(received.get_persistent("tenant"), Some("t1"));
+//! assert_eq!(received.get_persistent_bytes("auth").unwrap()[..], [0xde, 0xad]);
+//! ```
+
+use super::{Deadline, BASE64};
+use crate::{
+    BackwardRead, BackwardWrite, Budget, Category, ForwardRead, ForwardWrite, MetaInfo,
+    HTTP_PREFIX_BACKWARD, HTTP_PREFIX_BUDGET_PERSISTENT, HTTP_PREFIX_BUDGET_TRANSIENT,
+    HTTP_PREFIX_PERSISTENT, HTTP_PREFIX_TRANSIENT,
+};
+use base64::Engine;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+pub const GRPC_TIMEOUT: &str = "grpc-timeout";
+pub const BINARY_SUFFIX: &str = "-bin";
+
+/// The metadata of gRPC requests and responses.
+pub trait MetadataMap {
+    fn insert(&mut self, key: String, value: String);
+
+    fn entries(&self) -> Box<dyn Iterator<Item = (&str, &str)> + '_>;
+}
+
+impl MetadataMap for HashMap<String, String> {
+    #[inline]
+    fn insert(&mut self, key: String, value: String) {
+        HashMap::insert(self, key, value);
+    }
+
+    fn entries(&self) -> Box<dyn Iterator<Item = (&str, &str)> + '_> {
+        Box::new(self.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+    }
+}
+
+impl MetadataMap for Vec<(String, String)> {
+    #[inline]
+    fn insert(&mut self, key: String, value: String) {
+        self.push((key, value));
+    }
+
+    fn entries(&self) -> Box<dyn Iterator<Item = (&str, &str)> + '_> {
+        Box::new(self.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+    }
+}
+
+/// Errors of encoding metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The key contains characters not allowed in metadata keys, or is
+    /// suffixed by `-bin` while the value is not binary.
+    InvalidKey(String),
+    /// The value contains characters other than printable ASCII.
+    InvalidValue(String),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::InvalidKey(key) => write!(f, "invalid metadata key {:?}", key),
+            EncodeError::InvalidValue(key) => write!(f, "invalid metadata value of {:?}", key),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// Writes the persistents, transients and the deadline of the request.
+///
+/// Nothing is written if it fails.
+pub fn encode_request<M: MetadataMap + ?Sized>(
+    mi: &MetaInfo,
+    metadata: &mut M,
+) -> Result<(), EncodeError> {
+    let mut entries = Vec::new();
+    encode_request_entries(mi, &mut entries)?;
+    insert_all(metadata, entries);
+    Ok(())
+}
+
+fn encode_request_entries(
+    mi: &MetaInfo,
+    metadata: &mut Vec<(String, String)>,
+) -> Result<(), EncodeError> {
+    let now = SystemTime::now();
+    for (prefix, budget_prefix, category, map) in [
+        (
+            HTTP_PREFIX_PERSISTENT,
+            HTTP_PREFIX_BUDGET_PERSISTENT,
+            Category::Persistent,
+            mi.persistents(),
+        ),
+        (
+            HTTP_PREFIX_TRANSIENT,
+            HTTP_PREFIX_BUDGET_TRANSIENT,
+            Category::Transient,
+            mi.transients(),
+        ),
+    ] {
+        for (k, v) in map {
+            match mi.outbound_budget(category, k, now) {
+                Some(Some(budget)) => {
+                    put_text(metadata, budget_prefix, k, &budget.to_header_value())?
+                }
+                Some(None) => {}
+                None => continue,
+            }
+            put_text(metadata, prefix, k, v)?;
+        }
+        // the several values of a key are repeated entries
+        #[cfg(feature = "multi_value")]
+        for (k, v) in mi.extra_values(category) {
+            if mi.outbound_budget(category, k, now).is_some() {
+                put_text(metadata, prefix, k, v)?;
+            }
+        }
+    }
+    for (prefix, category) in [
+        (HTTP_PREFIX_PERSISTENT, Category::Persistent),
+        (HTTP_PREFIX_TRANSIENT, Category::Transient),
+    ] {
+        for (k, v) in mi.outbound_bytes_entries(category) {
+            put_binary(metadata, prefix, k, v)?;
+        }
+    }
+    if let Some(deadline) = mi.get::<Deadline>() {
+        metadata.push((
+            GRPC_TIMEOUT.to_owned(),
+            encode_timeout(deadline.remaining()),
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the request, sets the persistents, upstreams and the deadline.
+///
+/// The malformed entries are ignored.
+pub fn decode_request<M: MetadataMap + ?Sized>(metadata: &M, mi: &mut MetaInfo) {
+    let mut budgets = Vec::new();
+    #[cfg(feature = "multi_value")]
+    let mut seen = std::collections::HashSet::new();
+    for (key, value) in metadata.entries() {
+        let key = key.to_ascii_lowercase();
+        if key == GRPC_TIMEOUT {
+            if let Some(deadline) =
+                decode_timeout(value).and_then(|t| std::time::Instant::now().checked_add(t))
+            {
+                mi.insert(Deadline(deadline));
+            }
+        } else if let Some(key) = key.strip_suffix(BINARY_SUFFIX) {
+            for (prefix, category) in [
+                (HTTP_PREFIX_PERSISTENT, Category::Persistent),
+                (HTTP_PREFIX_TRANSIENT, Category::Upstream),
+            ] {
+                if let Some(key) = key.strip_prefix(prefix) {
+                    set_binary(mi, category, key, value);
+                }
+            }
+            continue;
+        }
+        // the repeated entries of a text key are its several values, while a
+        // bytes key has one value, which the last entry sets
+        #[cfg(feature = "multi_value")]
+        if !seen.insert(key.clone()) {
+            if let Some(k) = key.strip_prefix(HTTP_PREFIX_PERSISTENT) {
+                mi.append_inbound(Category::Persistent, k, value);
+            } else if let Some(k) = key.strip_prefix(HTTP_PREFIX_TRANSIENT) {
+                mi.append_inbound(Category::Upstream, k, value);
+            }
+            continue;
+        }
+        if let Some(k) = key.strip_prefix(HTTP_PREFIX_PERSISTENT) {
+            mi.set_inbound(Category::Persistent, k, value.to_owned().into());
+        } else if let Some(k) = key.strip_prefix(HTTP_PREFIX_TRANSIENT) {
+            mi.set_inbound(Category::Upstream, k, value.to_owned().into());
+        } else if let Some(k) = key.strip_prefix(HTTP_PREFIX_BUDGET_PERSISTENT) {
+            budgets.push((Category::Persistent, k.to_owned(), value));
+        } else if let Some(k) = key.strip_prefix(HTTP_PREFIX_BUDGET_TRANSIENT) {
+            budgets.push((Category::Upstream, k.to_owned(), value));
+        }
+    }
+    // the budgets apply to the values, which may come later
+    let now = SystemTime::now();
+    for (category, key, value) in budgets {
+        if let Some(budget) = Budget::parse_header_value(value) {
+            mi.set_inbound_budget(category, &key, budget, now);
+        }
+    }
+}
+
+/// Writes the backward transients of the response.
+///
+/// Nothing is written if it fails.
+pub fn encode_response<M: MetadataMap + ?Sized>(
+    mi: &MetaInfo,
+    metadata: &mut M,
+) -> Result<(), EncodeError> {
+    let mut entries = Vec::new();
+    encode_response_entries(mi, &mut entries)?;
+    insert_all(metadata, entries);
+    Ok(())
+}
+
+fn encode_response_entries(
+    mi: &MetaInfo,
+    metadata: &mut Vec<(String, String)>,
+) -> Result<(), EncodeError> {
+    let allowed = |(k, _): &(&str, &str)| mi.outbound_allows(Category::BackwardTransient, k);
+    for (k, v) in mi.backward_transients().filter(allowed) {
+        put_text(metadata, HTTP_PREFIX_BACKWARD, k, v)?;
+    }
+    #[cfg(feature = "multi_value")]
+    for (k, v) in mi.extra_values(Category::BackwardTransient).filter(allowed) {
+        put_text(metadata, HTTP_PREFIX_BACKWARD, k, v)?;
+    }
+    for (k, v) in mi.outbound_bytes_entries(Category::BackwardTransient) {
+        put_binary(metadata, HTTP_PREFIX_BACKWARD, k, v)?;
+    }
+    Ok(())
+}
+
+/// Reads the response, sets the backward downstreams.
+///
+/// The malformed entries are ignored.
+pub fn decode_response<M: MetadataMap + ?Sized>(metadata: &M, mi: &mut MetaInfo) {
+    #[cfg(feature = "multi_value")]
+    let mut seen = std::collections::HashSet::new();
+    for (key, value) in metadata.entries() {
+        let key = key.to_ascii_lowercase();
+        if let Some(key) = key.strip_suffix(BINARY_SUFFIX) {
+            if let Some(key) = key.strip_prefix(HTTP_PREFIX_BACKWARD) {
+                set_binary(mi, Category::BackwardDownstream, key, value);
+            }
+            continue;
+        }
+        #[cfg(feature = "multi_value")]
+        if !seen.insert(key.clone()) {
+            if let Some(k) = key.strip_prefix(HTTP_PREFIX_BACKWARD) {
+                mi.append_inbound(Category::BackwardDownstream, k, value);
+            }
+            continue;
+        }
+        if let Some(k) = key.strip_prefix(HTTP_PREFIX_BACKWARD) {
+            mi.set_inbound(Category::BackwardDownstream, k, value.to_owned().into());
+        }
+    }
+}
+
+fn insert_all<M: MetadataMap + ?Sized>(metadata: &mut M, entries: Vec<(String, String)>) {
+    for (key, value) in entries {
+        metadata.insert(key, value);
+    }
+}
+
+fn set_binary(mi: &mut MetaInfo, category: Category, key: &str, value: &str) {
+    if !mi.inbound_allows(category, key) {
+        return;
+    }
+    let value = match BASE64.decode(value) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+    let key = mi.inbound_key(category, key);
+    match category {
+        Category::Persistent => mi.set_persistent_bytes(key, value),
+        Category::Upstream => mi.set_upstream_bytes(key, value),
+        _ => mi.set_backward_downstream_bytes(key, value),
+    }
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .bytes()
+            .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'z' | b'_' | b'-' | b'.'))
+}
+
+fn put_text<M: MetadataMap + ?Sized>(
+    metadata: &mut M,
+    prefix: &str,
+    key: &str,
+    value: &str,
+) -> Result<(), EncodeError> {
+    let key = key.to_ascii_lowercase();
+    if !is_valid_key(&key) || key.ends_with(BINARY_SUFFIX) {
+        return Err(EncodeError::InvalidKey(key));
+    }
+    if !value.bytes().all(|b| (0x20..0x7f).contains(&b)) {
+        return Err(EncodeError::InvalidValue(key));
+    }
+    metadata.insert(format!("{}{}", prefix, key), value.to_owned());
+    Ok(())
+}
+
+fn put_binary<M: MetadataMap + ?Sized>(
+    metadata: &mut M,
+    prefix: &str,
+    key: &str,
+    value: &[u8],
+) -> Result<(), EncodeError> {
+    let key = key.to_ascii_lowercase();
+    if !is_valid_key(&key) {
+        return Err(EncodeError::InvalidKey(key));
+    }
+    metadata.insert(
+        format!("{}{}{}", prefix, key, BINARY_SUFFIX),
+        BASE64.encode(value),
+    );
+    Ok(())
+}
+
+// The value of `grpc-timeout` has at most 8 digits.
+const MAX_TIMEOUT_VALUE: u128 = 99_999_999;
+
+const TIMEOUT_UNITS: [(char, u128); 6] = [
+    ('n', 1),
+    ('u', 1_000),
+    ('m', 1_000_000),
+    ('S', 1_000_000_000),
+    ('M', 60_000_000_000),
+    ('H', 3_600_000_000_000),
+];
+
+/// Formats the timeout in the finest unit which fits in 8 digits.
+pub fn encode_timeout(timeout: Duration) -> String {
+    let nanos = timeout.as_nanos();
+    for (unit, nanos_per_unit) in TIMEOUT_UNITS {
+        let value = nanos / nanos_per_unit;
+        if value <= MAX_TIMEOUT_VALUE {
+            return format!("{}{}", value, unit);
+        }
+    }
+    format!("{}H", MAX_TIMEOUT_VALUE)
+}
+
+/// Parses the value of `grpc-timeout`, returns `None` if it's malformed.
+pub fn decode_timeout(value: &str) -> Option<Duration> {
+    // the unit may not be a single byte otherwise
+    if !value.is_ascii() {
+        return None;
+    }
+    let (digits, unit) = value.split_at(value.len().checked_sub(1)?);
+    if digits.is_empty() || digits.len() > 8 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let value: u64 = digits.parse().ok()?;
+    Some(match unit {
+        "n" => Duration::from_nanos(value),
+        "u" => Duration::from_micros(value),
+        "m" => Duration::from_millis(value),
+        "S" => Duration::from_secs(value),
+        "M" => Duration::from_secs(value * 60),
+        "H" => Duration::from_secs(value * 3600),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("Tenant", "t1");
+        mi.set_transient("caller", "svc");
+        mi.set_transient_bytes("ctx", vec![0, 1, 2, 255]);
+        mi.insert(Deadline::after(Duration::from_secs(10)));
+
+        let mut metadata = Vec::new();
+        encode_request(&mi, &mut metadata).unwrap();
+        let metadata: HashMap<_, _> = metadata.into_iter().collect();
+        assert_eq!(metadata["rpc-persist-tenant"], "t1");
+        assert_eq!(metadata["rpc-transit-caller"], "svc");
+        assert_eq!(metadata["rpc-transit-ctx-bin"], "AAEC/w");
+        assert!(decode_timeout(&metadata[GRPC_TIMEOUT]).unwrap() <= Duration::from_secs(10));
+
+        let mut received = MetaInfo::new();
+        decode_request(&metadata, &mut received);
+        assert_eq!(received.get_persistent("tenant"), Some("t1"));
+        assert_eq!(received.get_upstream("caller"), Some("svc"));
+        assert_eq!(
+            received.get_upstream_bytes("ctx").unwrap()[..],
+            [0, 1, 2, 255]
+        );
+        let remaining = received.get::<Deadline>().unwrap().remaining();
+        assert!(remaining > Duration::from_secs(9) && remaining <= Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_budget() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent_with_budget("p", "1", Budget::hops(3));
+        mi.set_persistent_with_budget("gone", "1", Budget::hops(0));
+
+        let mut metadata = HashMap::new();
+        encode_request(&mi, &mut metadata).unwrap();
+        assert_eq!(metadata.len(), 2);
+        assert_eq!(metadata["rpc-budget-persist-p"], "hops=2");
+
+        let mut received = MetaInfo::new();
+        decode_request(&metadata, &mut received);
+        assert_eq!(received.get_persistent_budget("p"), Some(&Budget::hops(2)));
+    }
+
+    #[test]
+    fn test_response() {
+        let mut mi = MetaInfo::new();
+        mi.set_backward_transient("cost", "10");
+        mi.set_backward_transient_bytes("trace", vec![1]);
+
+        let mut metadata = HashMap::new();
+        encode_response(&mi, &mut metadata).unwrap();
+        // padded base64 is accepted
+        metadata.insert("RPC-BACKWARD-TRACE-BIN".to_owned(), "AQ==".to_owned());
+
+        let mut received = MetaInfo::new();
+        decode_response(&metadata, &mut received);
+        assert_eq!(received.get_backward_downstream("cost"), Some("10"));
+        assert_eq!(
+            received.get_backward_downstream_bytes("trace").unwrap()[..],
+            [1]
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("a b", "v");
+        assert_eq!(
+            encode_request(&mi, &mut HashMap::new()),
+            Err(EncodeError::InvalidKey("a b".to_owned()))
+        );
+
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("k-bin", "v");
+        assert!(encode_request(&mi, &mut HashMap::new()).is_err());
+
+        // nothing is written before the invalid entry
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("a", "v");
+        mi.set_transient("k", "v\n");
+        let mut metadata = HashMap::new();
+        assert!(encode_request(&mi, &mut metadata).is_err());
+        assert!(metadata.is_empty());
+
+        let mut mi = MetaInfo::new();
+        mi.set_backward_transient("a", "v");
+        mi.set_backward_transient_bytes("b c", vec![1]);
+        let mut metadata = Vec::new();
+        assert!(encode_response(&mi, &mut metadata).is_err());
+        assert!(metadata.is_empty());
+
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("k", "v\n");
+        assert_eq!(
+            encode_request(&mi, &mut HashMap::new()),
+            Err(EncodeError::InvalidValue("k".to_owned()))
+        );
+
+        let metadata = HashMap::from([
+            ("rpc-persist-k-bin".to_owned(), "!!".to_owned()),
+            (GRPC_TIMEOUT.to_owned(), "1x".to_owned()),
+        ]);
+        let mut mi = MetaInfo::new();
+        decode_request(&metadata, &mut mi);
+        assert_eq!(mi.get_persistent_bytes("k"), None);
+        assert!(mi.get::<Deadline>().is_none());
+    }
+
+    #[cfg(feature = "multi_value")]
+    #[test]
+    fn test_multi_value() {
+        let mut mi = MetaInfo::new();
+        mi.append_transient("hops", "a");
+        mi.append_transient("hops", "b");
+
+        let mut metadata = Vec::new();
+        encode_request(&mi, &mut metadata).unwrap();
+        assert_eq!(metadata.len(), 2);
+
+        let mut received = MetaInfo::new();
+        decode_request(&metadata, &mut received);
+        assert_eq!(
+            received.get_all_values(Category::Upstream, "hops"),
+            ["a", "b"]
+        );
+    }
+
+    #[cfg(feature = "multi_value")]
+    #[test]
+    fn test_multi_value_binary() {
+        let metadata = vec![
+            ("rpc-persist-k-bin".to_owned(), "AQ".to_owned()),
+            ("rpc-persist-k-bin".to_owned(), "Ag".to_owned()),
+        ];
+        let mut received = MetaInfo::new();
+        decode_request(&metadata, &mut received);
+        // the last value of a bytes key is set
+        assert_eq!(received.get_persistent_bytes("k").unwrap()[..], [2]);
+        assert_eq!(received.get_persistent("k-bin"), None);
+        assert!(received
+            .get_all_values(Category::Persistent, "k-bin")
+            .is_empty());
+
+        let metadata = vec![
+            ("rpc-backward-k-bin".to_owned(), "AQ".to_owned()),
+            ("rpc-backward-k-bin".to_owned(), "Ag".to_owned()),
+        ];
+        let mut received = MetaInfo::new();
+        decode_response(&metadata, &mut received);
+        assert_eq!(
+            received.get_backward_downstream_bytes("k").unwrap()[..],
+            [2]
+        );
+        assert!(received
+            .get_all_values(Category::BackwardDownstream, "k-bin")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_timeout() {
+        assert_eq!(encode_timeout(Duration::from_nanos(5)), "5n");
+        assert_eq!(encode_timeout(Duration::from_millis(150)), "150000u");
+        assert_eq!(encode_timeout(Duration::from_secs(3600)), "3600000m");
+        assert_eq!(encode_timeout(Duration::from_secs(u64::MAX)), "99999999H");
+        assert_eq!(decode_timeout("150m"), Some(Duration::from_millis(150)));
+        assert_eq!(decode_timeout("2H"), Some(Duration::from_secs(7200)));
+        assert_eq!(decode_timeout("m"), None);
+        assert_eq!(decode_timeout("123456789m"), None);
+        assert_eq!(decode_timeout(""), None);
+        assert_eq!(decode_timeout("1é"), None);
+        assert_eq!(decode_timeout("é"), None);
+
+        let metadata = HashMap::from([(GRPC_TIMEOUT.to_owned(), "1é".to_owned())]);
+        let mut mi = MetaInfo::new();
+        decode_request(&metadata, &mut mi);
+        assert!(mi.get::<Deadline>().is_none());
+    }
+}
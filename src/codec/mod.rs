@@ -0,0 +1,54 @@
+//! Codecs which carry `MetaInfo` over the wire.
+//!
+//! On encoding, persistents, transients and backward transients are written.
+//! On decoding, they are set as persistents, upstreams and backward downstreams,
+//! following the `strip_*_prefix_and_set_*` methods.
+//!
+//! With the `bytes` feature, the bytes values are carried too: raw in binary
+//! frames, and base64 encoded in text headers.
+
+use std::time::{Duration, Instant};
+
+#[cfg(all(feature = "bytes", any(feature = "grpc", feature = "opentelemetry")))]
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+};
+
+pub mod binary;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod theader;
+
+/// The base64 engine of bytes values in text transports.
+///
+/// The values are encoded without padding as gRPC does, and decoded with or
+/// without padding.
+#[cfg(all(feature = "bytes", any(feature = "grpc", feature = "opentelemetry")))]
+pub(crate) const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// The deadline of the request, kept in `MetaInfo` as a typed entry.
+///
+/// It is carried by codecs whose transports have the concept, such as
+/// `grpc-timeout` of gRPC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deadline(pub Instant);
+
+impl Deadline {
+    /// Creates a deadline after the timeout from now.
+    #[inline]
+    pub fn after(timeout: Duration) -> Self {
+        Deadline(Instant::now() + timeout)
+    }
+
+    /// Returns the time left, or zero if the deadline has passed.
+    #[inline]
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+}
@@ -0,0 +1,478 @@
+//! Reads and writes the info sections of the Thrift THeader (TTHeader) transport.
+//!
+//! The string k-v are carried in the `INFO_KEYVALUE` section with the
+//! `RPC_PREFIX_*` prefixes, and the int k-v, whose keys are `u16`, in the
+//! `INFO_INTKEYVALUE` section. The int k-v are kept in `MetaInfo` as the typed
+//! entry [`IntKeyValues`].
+//!
+//! ```text
+//! INFO_KEYVALUE    := 0x01 count:u16 (len:u16 key len:u16 value){count}
+//! INFO_INTKEYVALUE := 0x10 count:u16 (key:u16 len:u16 value){count}
+//! ACL_TOKEN_KEYVALUE := 0x11 len:u16 token
+//! ```
+//!
+//! All integers are big-endian. Decoding stops at `INFO_PADDING` or at the end
+//! of the buffer. The ACL token section is skipped; an unknown info id is an
+//! error, as its section can't be skipped without knowing its layout. Neither
+//! encoding nor decoding changes anything if it fails.
+//!
+//! THeader has no section for bytes values, so they are not carried. Each key
+//! has one value, so only the first value of a key with several values is
+//! carried, see `JoinRule` to join them. The budgets are carried in the keys
+//! with the `RPC_PREFIX_BUDGET_*` prefixes, see [`Budget`].
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::codec::theader;
+//! use metainfo::{ForwardRead, ForwardWrite, MetaInfo};
+//!
+//! let mut mi = MetaInfo::new();
+//! mi.set_persistent("tenant", "t1");
+//!
+//! let mut buf = Vec::new();
+//! theader::encode_request(&mi, &mut buf).unwrap();
+//!
+//! let mut received = MetaInfo::new();
+//! theader::decode_request(&buf, &mut received).unwrap();
+//! assert_eq!(received.get_persistent("tenant"), Some("t1"));
+//! ```
+
+use crate::{BackwardRead, Budget, Category, ForwardRead, MetaInfo};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::SystemTime;
+
+pub const INFO_PADDING: u8 = 0x00;
+pub const INFO_KEYVALUE: u8 = 0x01;
+pub const INFO_INTKEYVALUE: u8 = 0x10;
+pub const ACL_TOKEN_KEYVALUE: u8 = 0x11;
+
+/// The int k-v of the `INFO_INTKEYVALUE` section.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntKeyValues(pub HashMap<u16, String>);
+
+/// Errors of encoding info sections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The key or value is longer than `u16::MAX` bytes.
+    TooLong(String),
+    /// There are more than `u16::MAX` entries in the section.
+    TooManyEntries(u8),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::TooLong(key) => write!(f, "the entry of {} is too long", key),
+            EncodeError::TooManyEntries(id) => {
+                write!(f, "too many entries in the info section {:#04x}", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// Errors of decoding info sections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    UnexpectedEof,
+    InvalidUtf8,
+    /// The info id is unknown, so the sections after are unreadable.
+    UnknownInfoId(u8),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEof => f.write_str("unexpected end of info sections"),
+            DecodeError::InvalidUtf8 => f.write_str("invalid utf-8 string"),
+            DecodeError::UnknownInfoId(id) => write!(f, "unknown info id {:#04x}", id),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Writes the persistents and transients of the request.
+pub fn encode_request(mi: &MetaInfo, buf: &mut Vec<u8>) -> Result<(), EncodeError> {
+    encode_or_truncate(buf, |buf| {
+        encode_request_sections(mi, buf)?;
+        encode_int_key_values(mi, buf)
+    })
+}
+
+fn encode_request_sections(mi: &MetaInfo, buf: &mut Vec<u8>) -> Result<(), EncodeError> {
+    let now = SystemTime::now();
+    let (mut kvs, mut budgets) = (Vec::new(), Vec::new());
+    for (prefix, budget_prefix, category, map) in [
+        (
+            crate::RPC_PREFIX_PERSISTENT,
+            crate::RPC_PREFIX_BUDGET_PERSISTENT,
+            Category::Persistent,
+            mi.persistents(),
+        ),
+        (
+            crate::RPC_PREFIX_TRANSIENT,
+            crate::RPC_PREFIX_BUDGET_TRANSIENT,
+            Category::Transient,
+            mi.transients(),
+        ),
+    ] {
+        for (k, v) in map {
+            match mi.outbound_budget(category, k, now) {
+                Some(Some(budget)) => budgets.push((budget_prefix, k, budget)),
+                Some(None) => {}
+                None => continue,
+            }
+            kvs.push((prefix, k, Cow::Borrowed(v)));
+        }
+    }
+    let budgets = budgets
+        .into_iter()
+        .map(|(prefix, k, budget)| (prefix, k, Cow::Owned(budget.to_header_value())));
+    encode_key_values(kvs.into_iter().chain(budgets), buf)
+}
+
+/// Reads the request, sets the persistents and upstreams.
+pub fn decode_request(buf: &[u8], mi: &mut MetaInfo) -> Result<(), DecodeError> {
+    let (kvs, int_kvs) = decode(buf)?;
+    let mut budgets = Vec::new();
+    for (key, value) in kvs {
+        if let Some(k) = key.strip_prefix(crate::RPC_PREFIX_PERSISTENT) {
+            mi.set_inbound(Category::Persistent, k, value.to_owned().into());
+        } else if let Some(k) = key.strip_prefix(crate::RPC_PREFIX_TRANSIENT) {
+            mi.set_inbound(Category::Upstream, k, value.to_owned().into());
+        } else if let Some(k) = key.strip_prefix(crate::RPC_PREFIX_BUDGET_PERSISTENT) {
+            budgets.push((Category::Persistent, k, value));
+        } else if let Some(k) = key.strip_prefix(crate::RPC_PREFIX_BUDGET_TRANSIENT) {
+            budgets.push((Category::Upstream, k, value));
+        }
+    }
+    // the budgets apply to the values, which may come later
+    let now = SystemTime::now();
+    for (category, key, value) in budgets {
+        if let Some(budget) = Budget::parse_header_value(value) {
+            mi.set_inbound_budget(category, key, budget, now);
+        }
+    }
+    insert_int_key_values(mi, int_kvs);
+    Ok(())
+}
+
+/// Writes the backward transients of the response.
+pub fn encode_response(mi: &MetaInfo, buf: &mut Vec<u8>) -> Result<(), EncodeError> {
+    encode_or_truncate(buf, |buf| {
+        let backwards = mi
+            .backward_transients()
+            .filter(|(k, _)| mi.outbound_allows(Category::BackwardTransient, k));
+        encode_key_values(
+            backwards.map(|(k, v)| (crate::RPC_PREFIX_BACKWARD, k, Cow::Borrowed(v))),
+            buf,
+        )?;
+        encode_int_key_values(mi, buf)
+    })
+}
+
+/// Reads the response, sets the backward downstreams.
+pub fn decode_response(buf: &[u8], mi: &mut MetaInfo) -> Result<(), DecodeError> {
+    let (kvs, int_kvs) = decode(buf)?;
+    for (key, value) in kvs {
+        if let Some(k) = key.strip_prefix(crate::RPC_PREFIX_BACKWARD) {
+            mi.set_inbound(Category::BackwardDownstream, k, value.to_owned().into());
+        }
+    }
+    insert_int_key_values(mi, int_kvs);
+    Ok(())
+}
+
+/// Removes the half-written sections if `encode` fails.
+fn encode_or_truncate<F>(buf: &mut Vec<u8>, encode: F) -> Result<(), EncodeError>
+where
+    F: FnOnce(&mut Vec<u8>) -> Result<(), EncodeError>,
+{
+    let start = buf.len();
+    let result = encode(buf);
+    if result.is_err() {
+        buf.truncate(start);
+    }
+    result
+}
+
+fn encode_key_values<'a, I>(kvs: I, buf: &mut Vec<u8>) -> Result<(), EncodeError>
+where
+    I: Iterator<Item = (&'static str, &'a str, Cow<'a, str>)>,
+{
+    let start = buf.len();
+    buf.push(INFO_KEYVALUE);
+    buf.extend_from_slice(&[0, 0]);
+    let mut count = 0u16;
+    for (prefix, key, value) in kvs {
+        count = count
+            .checked_add(1)
+            .ok_or(EncodeError::TooManyEntries(INFO_KEYVALUE))?;
+        let len = u16::try_from(prefix.len() + key.len())
+            .map_err(|_| EncodeError::TooLong(key.to_owned()))?;
+        buf.extend_from_slice(&len.to_be_bytes());
+        buf.extend_from_slice(prefix.as_bytes());
+        buf.extend_from_slice(key.as_bytes());
+        put_str(buf, key, &value)?;
+    }
+    if count == 0 {
+        buf.truncate(start);
+    } else {
+        buf[start + 1..start + 3].copy_from_slice(&count.to_be_bytes());
+    }
+    Ok(())
+}
+
+fn encode_int_key_values(mi: &MetaInfo, buf: &mut Vec<u8>) -> Result<(), EncodeError> {
+    let kvs = match mi.get::<IntKeyValues>() {
+        Some(kvs) if !kvs.0.is_empty() => &kvs.0,
+        _ => return Ok(()),
+    };
+    let count =
+        u16::try_from(kvs.len()).map_err(|_| EncodeError::TooManyEntries(INFO_INTKEYVALUE))?;
+    buf.push(INFO_INTKEYVALUE);
+    buf.extend_from_slice(&count.to_be_bytes());
+    for (key, value) in kvs {
+        buf.extend_from_slice(&key.to_be_bytes());
+        put_str(buf, &key.to_string(), value)?;
+    }
+    Ok(())
+}
+
+fn put_str(buf: &mut Vec<u8>, key: &str, value: &str) -> Result<(), EncodeError> {
+    let len = u16::try_from(value.len()).map_err(|_| EncodeError::TooLong(key.to_owned()))?;
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+type Sections<'a> = (Vec<(&'a str, &'a str)>, HashMap<u16, String>);
+
+/// Reads all the sections before applying any of them, so that nothing is set
+/// if a later section is malformed.
+fn decode(mut buf: &[u8]) -> Result<Sections<'_>, DecodeError> {
+    let (mut kvs, mut int_kvs) = (Vec::new(), HashMap::new());
+    while let Some((&id, rest)) = buf.split_first() {
+        buf = rest;
+        match id {
+            INFO_PADDING => break,
+            INFO_KEYVALUE => {
+                for _ in 0..take_u16(&mut buf)? {
+                    let key = take_str(&mut buf)?;
+                    let value = take_str(&mut buf)?;
+                    kvs.push((key, value));
+                }
+            }
+            INFO_INTKEYVALUE => {
+                for _ in 0..take_u16(&mut buf)? {
+                    let key = take_u16(&mut buf)?;
+                    let value = take_str(&mut buf)?;
+                    int_kvs.insert(key, value.to_owned());
+                }
+            }
+            // not carried in metainfo
+            ACL_TOKEN_KEYVALUE => {
+                take_bytes(&mut buf)?;
+            }
+            id => return Err(DecodeError::UnknownInfoId(id)),
+        }
+    }
+    Ok((kvs, int_kvs))
+}
+
+fn insert_int_key_values(mi: &mut MetaInfo, int_kvs: HashMap<u16, String>) {
+    if !int_kvs.is_empty() {
+        let mut kvs = mi.get::<IntKeyValues>().cloned().unwrap_or_default();
+        kvs.0.extend(int_kvs);
+        mi.insert(kvs);
+    }
+}
+
+fn take_u16(buf: &mut &[u8]) -> Result<u16, DecodeError> {
+    match buf {
+        [a, b, rest @ ..] => {
+            let n = u16::from_be_bytes([*a, *b]);
+            *buf = rest;
+            Ok(n)
+        }
+        _ => Err(DecodeError::UnexpectedEof),
+    }
+}
+
+fn take_bytes<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8], DecodeError> {
+    let len = take_u16(buf)? as usize;
+    if len > buf.len() {
+        return Err(DecodeError::UnexpectedEof);
+    }
+    let (bytes, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(bytes)
+}
+
+fn take_str<'a>(buf: &mut &'a [u8]) -> Result<&'a str, DecodeError> {
+    std::str::from_utf8(take_bytes(buf)?).map_err(|_| DecodeError::InvalidUtf8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BackwardWrite, ForwardWrite};
+
+    #[test]
+    fn test_request() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("p", "1");
+        mi.set_transient("t", "2");
+        mi.insert(IntKeyValues(HashMap::from([(8, "svc".to_owned())])));
+
+        let mut buf = Vec::new();
+        encode_request(&mi, &mut buf).unwrap();
+        // trailing padding is ignored
+        buf.extend_from_slice(&[INFO_PADDING, 0xff]);
+
+        let mut received = MetaInfo::new();
+        decode_request(&buf, &mut received).unwrap();
+        assert_eq!(received.get_persistent("p"), Some("1"));
+        assert_eq!(received.get_upstream("t"), Some("2"));
+        assert_eq!(received.get_transient("t"), None);
+        assert_eq!(received.get::<IntKeyValues>(), mi.get::<IntKeyValues>());
+    }
+
+    #[test]
+    fn test_budget() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent_with_budget("p", "1", Budget::hops(1));
+        mi.set_transient_with_budget("t", "1", Budget::hops(0));
+
+        let mut buf = Vec::new();
+        encode_request(&mi, &mut buf).unwrap();
+        let mut received = MetaInfo::new();
+        decode_request(&buf, &mut received).unwrap();
+        assert_eq!(received.get_upstream("t"), None);
+        assert_eq!(received.get_persistent_budget("p"), Some(&Budget::hops(0)));
+
+        // the last hop
+        let mut buf = Vec::new();
+        encode_request(&received, &mut buf).unwrap();
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_response() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("p", "1");
+        mi.set_backward_transient("b", "2");
+
+        let mut buf = Vec::new();
+        encode_response(&mi, &mut buf).unwrap();
+        let mut received = MetaInfo::new();
+        decode_response(&buf, &mut received).unwrap();
+        assert_eq!(received.get_backward_downstream("b"), Some("2"));
+        assert_eq!(received.get_persistent("p"), None);
+    }
+
+    #[test]
+    fn test_wire_format() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("k", "v");
+        let mut buf = Vec::new();
+        encode_request(&mi, &mut buf).unwrap();
+        let mut expected = vec![INFO_KEYVALUE, 0, 1, 0, 13];
+        expected.extend_from_slice(b"RPC_PERSIST_k");
+        expected.extend_from_slice(&[0, 1, b'v']);
+        assert_eq!(buf, expected);
+
+        // the keys without known prefixes are ignored
+        let buf = [INFO_KEYVALUE, 0, 1, 0, 1, b'k', 0, 1, b'v'];
+        let mut received = MetaInfo::new();
+        decode_request(&buf, &mut received).unwrap();
+        assert_eq!(received.get_persistent("k"), None);
+
+        let mut buf = Vec::new();
+        encode_request(&MetaInfo::new(), &mut buf).unwrap();
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut mi = MetaInfo::new();
+        assert_eq!(
+            decode_request(&[INFO_KEYVALUE, 0, 1, 0, 5, b'k'], &mut mi),
+            Err(DecodeError::UnexpectedEof)
+        );
+        let buf = [
+            INFO_INTKEYVALUE,
+            0,
+            1,
+            0,
+            1,
+            0,
+            1,
+            b'v',
+            INFO_KEYVALUE,
+            0,
+            1,
+        ];
+        assert_eq!(
+            decode_request(&buf, &mut mi),
+            Err(DecodeError::UnexpectedEof)
+        );
+        assert_eq!(mi.get::<IntKeyValues>(), None);
+        assert_eq!(
+            decode_request(&[INFO_INTKEYVALUE, 0, 1, 0, 1, 0, 1, 0xff], &mut mi),
+            Err(DecodeError::InvalidUtf8)
+        );
+
+        mi.set_persistent("a", "v");
+        mi.set_persistent("k", "v".repeat(u16::MAX as usize + 1));
+        let mut buf = vec![0xff];
+        assert_eq!(
+            encode_request(&mi, &mut buf),
+            Err(EncodeError::TooLong("k".to_owned()))
+        );
+        assert_eq!(buf, [0xff]);
+
+        let mut mi = MetaInfo::new();
+        mi.set_backward_transient("b", "v");
+        let kvs = (0..=u16::MAX).map(|k| (k, String::new())).collect();
+        mi.insert(IntKeyValues(kvs));
+        let mut buf = Vec::new();
+        assert_eq!(
+            encode_response(&mi, &mut buf),
+            Err(EncodeError::TooManyEntries(INFO_INTKEYVALUE))
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_info_id() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("k", "v");
+        let mut buf = Vec::new();
+        encode_request(&mi, &mut buf).unwrap();
+        // the token isn't utf-8, and is skipped
+        buf.extend_from_slice(&[ACL_TOKEN_KEYVALUE, 0, 1, 0xff]);
+        mi.set_persistent("k2", "v2");
+        encode_request(&mi, &mut buf).unwrap();
+
+        let mut received = MetaInfo::new();
+        decode_request(&buf, &mut received).unwrap();
+        assert_eq!(received.get_persistent("k"), Some("v"));
+        assert_eq!(received.get_persistent("k2"), Some("v2"));
+
+        let mut buf = Vec::new();
+        encode_request(&mi, &mut buf).unwrap();
+        buf.extend_from_slice(&[0x02, 0, 0]);
+        let mut received = MetaInfo::new();
+        assert_eq!(
+            decode_request(&buf, &mut received),
+            Err(DecodeError::UnknownInfoId(0x02))
+        );
+        // nothing is set if decoding fails
+        assert_eq!(received.get_persistent("k"), None);
+    }
+}
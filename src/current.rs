@@ -0,0 +1,206 @@
+//! The current `MetaInfo` of the running task or thread.
+//!
+//! The task-local [`METAINFO`](crate::METAINFO) is only available inside tokio
+//! tasks. For synchronous code, worker threads of other runtimes and blocking
+//! closures, a `MetaInfo` can be set as the current one of the thread by
+//! [`set_current`]. The helpers in this module look up the task-local first,
+//! and then the thread-local.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::current::{set_current, with_current};
+//! use metainfo::{ForwardRead, ForwardWrite, MetaInfo};
+//!
+//! let mut mi = MetaInfo::new();
+//! mi.set_persistent("tenant", "t1");
+//!
+//! let guard = set_current(mi);
+//! let tenant = with_current(|mi| mi.and_then(|mi| mi.get_persistent("tenant").map(String::from)));
+//! assert_eq!(tenant.as_deref(), Some("t1"));
+//! let _mi = guard.finish();
+//! ```
+
+use crate::MetaInfo;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+
+thread_local! {
+    static CURRENT: RefCell<Option<MetaInfo>> = const { RefCell::new(None) };
+}
+
+/// Restores the previous current `MetaInfo` of the thread when dropped.
+///
+/// The guard must be dropped on the thread where it is created.
+#[must_use = "the MetaInfo is unset when the guard is dropped"]
+pub struct CurrentGuard {
+    prev: Option<MetaInfo>,
+    // the guard is bound to the thread
+    _marker: PhantomData<*const ()>,
+}
+
+impl CurrentGuard {
+    /// Restores the previous current `MetaInfo`, and returns the one set by
+    /// this guard, including the changes made while it was current.
+    pub fn finish(mut self) -> MetaInfo {
+        let prev = self.prev.take();
+        let mi = CURRENT.with(|current| std::mem::replace(&mut *current.borrow_mut(), prev));
+        std::mem::forget(self);
+        mi.unwrap_or_default()
+    }
+}
+
+impl Drop for CurrentGuard {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        CURRENT.with(|current| *current.borrow_mut() = prev);
+    }
+}
+
+/// Sets the current `MetaInfo` of the thread until the returned guard is
+/// dropped or finished.
+pub fn set_current(mi: MetaInfo) -> CurrentGuard {
+    let prev = CURRENT.with(|current| current.borrow_mut().replace(mi));
+    CurrentGuard {
+        prev,
+        _marker: PhantomData,
+    }
+}
+
+/// Calls the closure with the current `MetaInfo`, or `None` if there is none.
+///
+/// The closure also gets `None` if the `MetaInfo` is mutably borrowed, e.g.
+/// when called inside [`with_current_mut`].
+pub fn with_current<R, F: FnOnce(Option<&MetaInfo>) -> R>(f: F) -> R {
+    let mut f = Some(f);
+    #[cfg(feature = "task_local")]
+    if let Ok(r) =
+        crate::METAINFO.try_with(|mi| (f.take().unwrap())(mi.try_borrow().ok().as_deref()))
+    {
+        return r;
+    }
+    CURRENT.with(|current| {
+        let current = current.try_borrow().ok();
+        (f.take().unwrap())(current.as_ref().and_then(|mi| mi.as_ref()))
+    })
+}
+
+/// Calls the closure with the mutable current `MetaInfo`, or `None` if there is none.
+///
+/// The closure also gets `None` if the `MetaInfo` is borrowed, e.g. when called
+/// inside [`with_current`] or [`with_current_mut`].
+pub fn with_current_mut<R, F: FnOnce(Option<&mut MetaInfo>) -> R>(f: F) -> R {
+    let mut f = Some(f);
+    #[cfg(feature = "task_local")]
+    if let Ok(r) =
+        crate::METAINFO.try_with(|mi| (f.take().unwrap())(mi.try_borrow_mut().ok().as_deref_mut()))
+    {
+        return r;
+    }
+    CURRENT.with(|current| {
+        let mut current = current.try_borrow_mut().ok();
+        (f.take().unwrap())(current.as_mut().and_then(|mi| mi.as_mut()))
+    })
+}
+
+/// Derives the current `MetaInfo`, returns the child, or `None` if there is none.
+pub fn derive_current() -> Option<MetaInfo> {
+    with_current_mut(|mi| {
+        mi.map(|mi| {
+            let (current, child) = std::mem::take(mi).derive();
+            *mi = current;
+            child
+        })
+    })
+}
+
+/// Runs the closure on the blocking thread pool of tokio, with a child
+/// derived from the current `MetaInfo` set as the current one of the thread.
+#[cfg(feature = "task_local")]
+pub fn spawn_blocking_with_metainfo<F, R>(f: F) -> tokio::task::JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let mi = derive_current();
+    tokio::task::spawn_blocking(move || match mi {
+        Some(mi) => {
+            let _guard = set_current(mi);
+            f()
+        }
+        None => f(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BackwardRead, BackwardWrite, ForwardRead, ForwardWrite};
+
+    fn current_persistent(key: &str) -> Option<String> {
+        with_current(|mi| mi.and_then(|mi| mi.get_persistent(key).map(String::from)))
+    }
+
+    #[test]
+    fn test_thread_local() {
+        assert_eq!(current_persistent("k"), None);
+
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("k", "outer");
+        let outer = set_current(mi);
+        {
+            let mut mi = MetaInfo::new();
+            mi.set_persistent("k", "inner");
+            let _inner = set_current(mi);
+            assert_eq!(current_persistent("k"), Some("inner".to_owned()));
+        }
+        assert_eq!(current_persistent("k"), Some("outer".to_owned()));
+
+        with_current_mut(|mi| {
+            mi.unwrap().set_backward_transient("k", "v");
+            // borrowed
+            assert_eq!(current_persistent("k"), None);
+        });
+        let child = derive_current().unwrap();
+        assert_eq!(child.get_persistent("k"), Some("outer"));
+
+        let mi = outer.finish();
+        assert_eq!(current_persistent("k"), None);
+        assert_eq!(mi.get_backward_transient("k"), Some("v"));
+    }
+
+    #[cfg(feature = "task_local")]
+    #[test]
+    fn test_task_local_first() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("k", "thread");
+        let _guard = set_current(mi);
+
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("k", "task");
+        crate::METAINFO.sync_scope(RefCell::new(mi), || {
+            assert_eq!(current_persistent("k"), Some("task".to_owned()));
+            crate::METAINFO.with(|mi| {
+                let _mi = mi.borrow_mut();
+                assert_eq!(current_persistent("k"), None);
+                assert!(with_current_mut(|mi| mi.is_none()));
+            });
+        });
+        assert_eq!(current_persistent("k"), Some("thread".to_owned()));
+    }
+
+    #[cfg(feature = "task_local")]
+    #[test]
+    fn test_spawn_blocking() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("k", "v");
+        let value = rt.block_on(crate::METAINFO.scope(RefCell::new(mi), async {
+            spawn_blocking_with_metainfo(|| current_persistent("k"))
+                .await
+                .unwrap()
+        }));
+        assert_eq!(value, Some("v".to_owned()));
+    }
+}
@@ -0,0 +1,404 @@
+//! Differences between two states of `MetaInfo`.
+//!
+//! [`MetaInfo::diff`] compares the text and bytes k-v of every [`Category`] in
+//! the current scope, with the string k-v resolved through the parents as by
+//! `get_string`, and [`MetaInfo::apply`] replays the differences on another
+//! `MetaInfo`. The values of sensitive keys are redacted in the printed form.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::{BackwardRead, BackwardWrite, Category, ForwardRead, ForwardWrite, MetaInfo};
+//!
+//! let mut mi = MetaInfo::new();
+//! mi.set_persistent("tenant", "t1");
+//! let (before, mut mi) = mi.derive();
+//!
+//! mi.set_persistent("tenant", "t2");
+//! mi.set_backward_transient("cost", "3");
+//! let mut diff = before.diff(&mi);
+//! assert_eq!(diff.to_string(), "~ persistent tenant: t1 -> t2\n+ backward_transient cost=3\n");
+//!
+//! // only send the backward changes
+//! diff.retain(|category, _| category == Category::BackwardTransient);
+//! let mut out = MetaInfo::new();
+//! out.apply(&diff);
+//! assert_eq!(out.get_backward_transient("cost"), Some("3"));
+//! assert_eq!(out.get_persistent("tenant"), None);
+//! ```
+
+use crate::observe::Value;
+use crate::redact::REDACTED;
+#[cfg(feature = "bytes")]
+use crate::{BackwardWrite, ForwardWrite};
+use crate::{Category, MetaInfo};
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A value in a [`Change`], printed as the text or the escaped bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffValue {
+    Text(String),
+    #[cfg(feature = "bytes")]
+    Bytes(Bytes),
+}
+
+impl DiffValue {
+    /// Returns the text, or `None` if it's a bytes value.
+    #[inline]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            DiffValue::Text(text) => Some(text),
+            #[cfg(feature = "bytes")]
+            DiffValue::Bytes(_) => None,
+        }
+    }
+}
+
+impl From<Value<'_>> for DiffValue {
+    fn from(value: Value<'_>) -> Self {
+        match value {
+            Value::Text(text) => DiffValue::Text(text.to_owned()),
+            #[cfg(feature = "bytes")]
+            Value::Bytes(bytes) => DiffValue::Bytes(bytes.clone()),
+        }
+    }
+}
+
+impl fmt::Display for DiffValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffValue::Text(text) => f.write_str(text),
+            #[cfg(feature = "bytes")]
+            DiffValue::Bytes(bytes) => write!(f, "{:?}", bytes),
+        }
+    }
+}
+
+/// The change of a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added(DiffValue),
+    Removed(DiffValue),
+    Changed { old: DiffValue, new: DiffValue },
+}
+
+/// A change of a key in a category.
+#[derive(Clone, PartialEq, Eq)]
+pub struct DiffEntry {
+    category: Category,
+    key: String,
+    change: Change,
+    sensitive: bool,
+}
+
+impl DiffEntry {
+    #[inline]
+    pub fn category(&self) -> Category {
+        self.category
+    }
+
+    #[inline]
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    #[inline]
+    pub fn change(&self) -> &Change {
+        &self.change
+    }
+}
+
+impl fmt::Debug for DiffEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let change = if self.sensitive {
+            let redacted = || DiffValue::Text(REDACTED.to_owned());
+            match &self.change {
+                Change::Added(_) => Change::Added(redacted()),
+                Change::Removed(_) => Change::Removed(redacted()),
+                Change::Changed { .. } => Change::Changed {
+                    old: redacted(),
+                    new: redacted(),
+                },
+            }
+        } else {
+            self.change.clone()
+        };
+        f.debug_struct("DiffEntry")
+            .field("category", &self.category)
+            .field("key", &self.key)
+            .field("change", &change)
+            .finish()
+    }
+}
+
+impl fmt::Display for DiffEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let category = self.category.as_str();
+        let key = &self.key;
+        let redact = |v: &DiffValue| -> String {
+            if self.sensitive {
+                REDACTED.to_owned()
+            } else {
+                v.to_string()
+            }
+        };
+        match &self.change {
+            Change::Added(v) => write!(f, "+ {} {}={}", category, key, redact(v)),
+            Change::Removed(v) => write!(f, "- {} {}={}", category, key, redact(v)),
+            Change::Changed { old, new } => write!(
+                f,
+                "~ {} {}: {} -> {}",
+                category,
+                key,
+                redact(old),
+                redact(new)
+            ),
+        }
+    }
+}
+
+/// The changes from one `MetaInfo` to another, ordered by category and key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MetaInfoDiff {
+    entries: Vec<DiffEntry>,
+}
+
+impl MetaInfoDiff {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &DiffEntry> {
+        self.entries.iter()
+    }
+
+    /// Iterates the changes of the given category.
+    pub fn category(&self, category: Category) -> impl Iterator<Item = &DiffEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.category == category)
+    }
+
+    /// Keeps only the changes for which the predicate returns true.
+    pub fn retain<F: FnMut(Category, &str) -> bool>(&mut self, mut f: F) {
+        self.entries.retain(|entry| f(entry.category, &entry.key))
+    }
+}
+
+impl<'a> IntoIterator for &'a MetaInfoDiff {
+    type Item = &'a DiffEntry;
+    type IntoIter = std::slice::Iter<'a, DiffEntry>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+impl fmt::Display for MetaInfoDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the text and bytes k-v of the category, with the string k-v resolved
+/// through the parents.
+fn values(mi: &MetaInfo, category: Category) -> HashMap<&str, Value<'_>> {
+    let mut values = HashMap::new();
+    if category == Category::String {
+        let mut scope = Some(mi);
+        while let Some(mi) = scope {
+            for (k, v) in mi.entries(category) {
+                values.entry(k).or_insert(Value::Text(v));
+            }
+            scope = mi.parent.as_deref();
+        }
+        return values;
+    }
+    values.extend(mi.entries(category).map(|(k, v)| (k, Value::Text(v))));
+    #[cfg(feature = "bytes")]
+    values.extend(
+        mi.bytes_entries(category)
+            .map(|(k, v)| (k, Value::Bytes(v))),
+    );
+    values
+}
+
+impl MetaInfo {
+    /// Returns the changes which turn the k-v of this `MetaInfo` into those of
+    /// `other`.
+    pub fn diff(&self, other: &MetaInfo) -> MetaInfoDiff {
+        let mut entries = Vec::new();
+        for category in Category::ALL {
+            let mut theirs = values(other, category);
+            let start = entries.len();
+            for (k, v) in values(self, category) {
+                let change = match theirs.remove(k) {
+                    Some(new) if new == v => continue,
+                    Some(new) => Change::Changed {
+                        old: v.into(),
+                        new: new.into(),
+                    },
+                    None => Change::Removed(v.into()),
+                };
+                entries.push(DiffEntry {
+                    category,
+                    key: k.to_owned(),
+                    change,
+                    sensitive: self.is_sensitive(k) || other.is_sensitive(k),
+                });
+            }
+            for (k, v) in theirs {
+                entries.push(DiffEntry {
+                    category,
+                    key: k.to_owned(),
+                    change: Change::Added(v.into()),
+                    sensitive: self.is_sensitive(k) || other.is_sensitive(k),
+                });
+            }
+            entries[start..].sort_by(|a, b| a.key.cmp(&b.key));
+        }
+        MetaInfoDiff { entries }
+    }
+
+    fn set_value_by_category(&mut self, category: Category, key: String, value: DiffValue) {
+        match value {
+            DiffValue::Text(text) => self.set_by_category(category, key, text),
+            #[cfg(feature = "bytes")]
+            DiffValue::Bytes(v) => match category {
+                Category::Persistent => self.set_persistent_bytes(key, v),
+                Category::Transient => self.set_transient_bytes(key, v),
+                Category::Upstream => self.set_upstream_bytes(key, v),
+                Category::BackwardTransient => self.set_backward_transient_bytes(key, v),
+                Category::BackwardDownstream => self.set_backward_downstream_bytes(key, v),
+                Category::String => unreachable!("the string k-v only hold text values"),
+            },
+        }
+    }
+
+    /// Replays the changes on this `MetaInfo`.
+    pub fn apply(&mut self, diff: &MetaInfoDiff) {
+        for entry in &diff.entries {
+            match &entry.change {
+                Change::Added(v) | Change::Changed { new: v, .. } => {
+                    self.set_value_by_category(entry.category, entry.key.clone(), v.clone())
+                }
+                Change::Removed(_) => self.del_by_category(entry.category, &entry.key),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::Pattern;
+    use crate::{BackwardWrite, ForwardRead, ForwardWrite};
+
+    #[test]
+    fn test_diff() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("a", "1");
+        mi.set_persistent("b", "2");
+        mi.set_transient("t", "x");
+        mi.insert_string("s".into(), "v".into());
+
+        let mut other = MetaInfo::new();
+        other.set_persistent("a", "1");
+        other.set_persistent("b", "3");
+        other.set_persistent("c", "4");
+        other.set_backward_downstream("d", "5");
+        other.insert_string("s".into(), "w".into());
+
+        let diff = mi.diff(&other);
+        assert_eq!(diff.len(), 5);
+        assert_eq!(
+            diff.to_string(),
+            "~ persistent b: 2 -> 3\n\
+             + persistent c=4\n\
+             - transient t=x\n\
+             + backward_downstream d=5\n\
+             ~ string s: v -> w\n"
+        );
+        assert_eq!(
+            diff.category(Category::Transient)
+                .map(|e| e.change().clone())
+                .collect::<Vec<_>>(),
+            [Change::Removed(DiffValue::Text("x".to_owned()))]
+        );
+
+        mi.apply(&diff);
+        assert!(mi.diff(&other).is_empty());
+        assert!(other.diff(&other).is_empty());
+    }
+
+    #[test]
+    fn test_diff_redacted() {
+        let mut mi = MetaInfo::new();
+        mi.mark_sensitive(Pattern::exact("token"));
+        let (before, mut mi) = mi.derive();
+        mi.set_transient("token", "s3cr3t");
+
+        let diff = before.diff(&mi);
+        assert_eq!(diff.to_string(), "+ transient token=<redacted>\n");
+        assert!(!format!("{:?}", diff).contains("s3cr3t"));
+
+        let mut other = MetaInfo::new();
+        other.apply(&diff);
+        assert_eq!(other.get_transient("token"), Some("s3cr3t"));
+    }
+
+    #[test]
+    fn test_diff_string_parent() {
+        let mut mi = MetaInfo::new();
+        mi.insert_string("s".into(), "v".into());
+        mi.insert_string("t".into(), "v".into());
+        let (before, mut mi) = mi.derive();
+        mi.insert_string("t".into(), "w".into());
+
+        // the value of the parent is not added, and the shadowing one changes it
+        assert_eq!(before.diff(&mi).to_string(), "~ string t: v -> w\n");
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_diff_bytes() {
+        use crate::ForwardRead;
+
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("p", "text");
+        mi.set_transient_bytes("t", vec![1]);
+        let (before, mut mi) = mi.derive();
+        mi.set_persistent_bytes("p", vec![0xff]);
+        mi.set_upstream_bytes("u", vec![2]);
+        mi.del_transient_bytes("t");
+
+        let diff = before.diff(&mi);
+        assert_eq!(
+            diff.to_string(),
+            "~ persistent p: text -> b\"\\xff\"\n\
+             - transient t=b\"\\x01\"\n\
+             + upstream u=b\"\\x02\"\n"
+        );
+
+        let mut other = MetaInfo::new();
+        other.set_persistent("p", "text");
+        other.apply(&diff);
+        assert_eq!(other.get_persistent("p"), None);
+        assert_eq!(other.get_persistent_bytes("p").unwrap()[..], [0xff]);
+        assert_eq!(other.get_upstream_bytes("u").unwrap()[..], [2]);
+        assert!(other.diff(&mi).is_empty());
+    }
+}
@@ -0,0 +1,55 @@
+use crate::{Category, MetaInfo};
+use std::borrow::Cow;
+
+/// A metainfo key selected to be recorded in logs or traces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub(crate) category: Category,
+    pub(crate) key: Cow<'static, str>,
+    pub(crate) name: Cow<'static, str>,
+}
+
+impl Field {
+    /// Creates a `Field` of the key in the category, named after the key.
+    pub fn new<K: Into<Cow<'static, str>>>(category: Category, key: K) -> Self {
+        let key = key.into();
+        Field {
+            category,
+            name: key.clone(),
+            key,
+        }
+    }
+
+    pub fn persistent<K: Into<Cow<'static, str>>>(key: K) -> Self {
+        Self::new(Category::Persistent, key)
+    }
+
+    pub fn transient<K: Into<Cow<'static, str>>>(key: K) -> Self {
+        Self::new(Category::Transient, key)
+    }
+
+    pub fn string<K: Into<Cow<'static, str>>>(key: K) -> Self {
+        Self::new(Category::String, key)
+    }
+
+    /// Records the key under another field name.
+    pub fn rename<N: Into<Cow<'static, str>>>(mut self, name: N) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the value of the key in the `MetaInfo`, redacted if sensitive.
+    pub fn value<'a>(&self, mi: &'a MetaInfo) -> Option<&'a str> {
+        let value = mi.get_by_category(self.category, &self.key)?;
+        if mi.is_sensitive(&self.key) {
+            Some(crate::redact::REDACTED)
+        } else {
+            Some(value)
+        }
+    }
+}
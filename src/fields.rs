@@ -0,0 +1,135 @@
+//! Mapping structs to string metainfo.
+//!
+//! With the `derive` feature, [`MetaInfoFields`] can be derived for structs
+//! with named fields.
+//!
+//! Examples:
+//! ```rust
+//! # #[cfg(feature = "derive")]
+//! # {
+//! use metainfo::{ForwardRead, MetaInfo, MetaInfoFields};
+//!
+//! #[derive(Debug, PartialEq, MetaInfoFields)]
+//! struct CallerInfo {
+//!     #[metainfo(persistent, key = "caller_svc")]
+//!     service: String,
+//!     #[metainfo(transient)]
+//!     cluster: Option<String>,
+//! }
+//!
+//! let caller = CallerInfo {
+//!     service: "svc".to_owned(),
+//!     cluster: None,
+//! };
+//! let mut mi = MetaInfo::new();
+//! caller.write_to(&mut mi);
+//! assert_eq!(mi.get_persistent("caller_svc"), Some("svc"));
+//! assert_eq!(CallerInfo::read_from(&mi), Ok(caller));
+//! # }
+//! ```
+
+use crate::{Category, MetaInfo, ParseError};
+use std::fmt;
+
+#[cfg(feature = "derive")]
+pub use metainfo_derive::MetaInfoFields;
+
+/// A struct whose fields are mapped to string metainfo.
+pub trait MetaInfoFields: Sized {
+    /// Writes the fields into the `MetaInfo`.
+    fn write_to(&self, mi: &mut MetaInfo);
+
+    /// Reads the fields from the `MetaInfo`.
+    fn read_from(mi: &MetaInfo) -> Result<Self, FieldError>;
+}
+
+/// Failed to read a struct from `MetaInfo`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldError {
+    /// A required field is absent.
+    Missing {
+        key: &'static str,
+        category: Category,
+    },
+    /// A field failed to parse.
+    Parse(ParseError),
+}
+
+impl From<ParseError> for FieldError {
+    #[inline]
+    fn from(err: ParseError) -> Self {
+        FieldError::Parse(err)
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldError::Missing { key, category } => {
+                write!(f, "missing {} metainfo {}", category.as_str(), key)
+            }
+            FieldError::Parse(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for FieldError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FieldError::Missing { .. } => None,
+            FieldError::Parse(err) => Some(err),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use super::*;
+    use crate::{BackwardRead, BackwardWrite, ForwardRead, ForwardWrite};
+
+    #[derive(Debug, PartialEq, MetaInfoFields)]
+    struct Caller {
+        #[metainfo(key = "caller_svc")]
+        service: String,
+        #[metainfo(string)]
+        cluster: Option<String>,
+        #[metainfo(backward_transient, key = "caller_retries")]
+        retries: u8,
+        #[metainfo(skip)]
+        local: Vec<u8>,
+    }
+
+    #[test]
+    fn test_derive() {
+        let mut caller = Caller {
+            service: "svc".to_owned(),
+            cluster: Some("c1".to_owned()),
+            retries: 3,
+            local: vec![1],
+        };
+        let mut mi = MetaInfo::new();
+        caller.write_to(&mut mi);
+        assert_eq!(mi.get_persistent("caller_svc"), Some("svc"));
+        assert_eq!(mi.get_string("cluster").map(|v| v.as_ref()), Some("c1"));
+        assert_eq!(mi.get_backward_transient("caller_retries"), Some("3"));
+
+        caller.local = Vec::new();
+        assert_eq!(Caller::read_from(&mi), Ok(caller));
+
+        mi.remove_string("cluster");
+        assert_eq!(Caller::read_from(&mi).unwrap().cluster, None);
+
+        mi.set_backward_transient("caller_retries", "x");
+        let err = Caller::read_from(&mi).unwrap_err();
+        assert!(matches!(err, FieldError::Parse(ref e) if e.key() == "caller_retries"));
+
+        mi.del_persistent("caller_svc");
+        assert_eq!(
+            Caller::read_from(&mi),
+            Err(FieldError::Missing {
+                key: "caller_svc",
+                category: Category::Persistent
+            })
+        );
+    }
+}
@@ -1,14 +1,89 @@
-use std::{borrow::Cow, collections::HashMap};
+#[cfg(feature = "bytes")]
+use crate::kv::BytesEntries;
+use crate::kv::Entries;
+use paste::paste;
+use std::borrow::Cow;
+use std::collections::HashMap;
 
-pub trait Forward {
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+
+/// Forward info that is passed to the downstream: both the read and the write half.
+///
+/// Implemented for every type implementing [`ForwardRead`] and [`ForwardWrite`].
+/// Its methods are those of the halves, so importing `Forward` alone doesn't
+/// make them callable; import the halves or [`prelude`](crate::prelude).
+pub trait Forward: ForwardRead + ForwardWrite {}
+
+impl<T: ForwardRead + ForwardWrite + ?Sized> Forward for T {}
+
+/// The read half of [`Forward`].
+pub trait ForwardRead {
     fn get_persistent<K: AsRef<str>>(&self, key: K) -> Option<&str>;
     fn get_transient<K: AsRef<str>>(&self, key: K) -> Option<&str>;
     fn get_upstream<K: AsRef<str>>(&self, key: K) -> Option<&str>;
 
-    fn get_all_persistents(&self) -> Option<&HashMap<Cow<'static, str>, Cow<'static, str>>>;
-    fn get_all_transients(&self) -> Option<&HashMap<Cow<'static, str>, Cow<'static, str>>>;
-    fn get_all_upstreams(&self) -> Option<&HashMap<Cow<'static, str>, Cow<'static, str>>>;
+    /// Iterates the persistents; `len` and `is_empty` count them.
+    fn persistents(&self) -> Entries<'_>;
+    /// Iterates the transients; `len` and `is_empty` count them.
+    fn transients(&self) -> Entries<'_>;
+    /// Iterates the upstreams; `len` and `is_empty` count them.
+    fn upstreams(&self) -> Entries<'_>;
+
+    #[cfg(feature = "bytes")]
+    fn get_persistent_bytes<K: AsRef<str>>(&self, key: K) -> Option<&Bytes>;
+    #[cfg(feature = "bytes")]
+    fn get_transient_bytes<K: AsRef<str>>(&self, key: K) -> Option<&Bytes>;
+    #[cfg(feature = "bytes")]
+    fn get_upstream_bytes<K: AsRef<str>>(&self, key: K) -> Option<&Bytes>;
+
+    /// Iterates the bytes values of the persistents.
+    #[cfg(feature = "bytes")]
+    fn persistent_bytes(&self) -> BytesEntries<'_>;
+    /// Iterates the bytes values of the transients.
+    #[cfg(feature = "bytes")]
+    fn transient_bytes(&self) -> BytesEntries<'_>;
+    /// Iterates the bytes values of the upstreams.
+    #[cfg(feature = "bytes")]
+    fn upstream_bytes(&self) -> BytesEntries<'_>;
+
+    // the std maps built on each call, for the code written before the iterators
+    #[deprecated(note = "use `persistents`, which doesn't expose the storage")]
+    fn get_all_persistents(&self) -> Option<HashMap<Cow<'static, str>, Cow<'static, str>>> {
+        self.persistents().into_hash_map()
+    }
+
+    #[deprecated(note = "use `transients`, which doesn't expose the storage")]
+    fn get_all_transients(&self) -> Option<HashMap<Cow<'static, str>, Cow<'static, str>>> {
+        self.transients().into_hash_map()
+    }
+
+    #[deprecated(note = "use `upstreams`, which doesn't expose the storage")]
+    fn get_all_upstreams(&self) -> Option<HashMap<Cow<'static, str>, Cow<'static, str>>> {
+        self.upstreams().into_hash_map()
+    }
+
+    #[cfg(feature = "bytes")]
+    #[deprecated(note = "use `persistent_bytes`, which doesn't expose the storage")]
+    fn get_all_persistent_bytes(&self) -> Option<HashMap<Cow<'static, str>, Bytes>> {
+        self.persistent_bytes().into_hash_map()
+    }
 
+    #[cfg(feature = "bytes")]
+    #[deprecated(note = "use `transient_bytes`, which doesn't expose the storage")]
+    fn get_all_transient_bytes(&self) -> Option<HashMap<Cow<'static, str>, Bytes>> {
+        self.transient_bytes().into_hash_map()
+    }
+
+    #[cfg(feature = "bytes")]
+    #[deprecated(note = "use `upstream_bytes`, which doesn't expose the storage")]
+    fn get_all_upstream_bytes(&self) -> Option<HashMap<Cow<'static, str>, Bytes>> {
+        self.upstream_bytes().into_hash_map()
+    }
+}
+
+/// The write half of [`Forward`].
+pub trait ForwardWrite {
     fn set_persistent<K: Into<Cow<'static, str>>, V: Into<Cow<'static, str>>>(
         &mut self,
         key: K,
@@ -25,16 +100,29 @@ pub trait Forward {
         value: V,
     );
 
-    fn strip_rpc_prefix_and_set_persistent<K: Into<Cow<'static, str>>, V: Into<Cow<'static, str>>>(
+    fn strip_rpc_prefix_and_set_persistent<
+        K: Into<Cow<'static, str>>,
+        V: Into<Cow<'static, str>>,
+    >(
         &mut self,
         key: K,
         value: V,
-    );
+    ) {
+        let key: Cow<'static, str> = key.into();
+        if let Some(key) = key.strip_prefix(crate::RPC_PREFIX_PERSISTENT) {
+            self.set_persistent(crate::intern::lookup(key), value);
+        }
+    }
     fn strip_rpc_prefix_and_set_upstream<K: Into<Cow<'static, str>>, V: Into<Cow<'static, str>>>(
         &mut self,
         key: K,
         value: V,
-    );
+    ) {
+        let key: Cow<'static, str> = key.into();
+        if let Some(key) = key.strip_prefix(crate::RPC_PREFIX_TRANSIENT) {
+            self.set_upstream(crate::intern::lookup(key), value);
+        }
+    }
 
     fn strip_http_prefix_and_set_persistent<
         K: Into<Cow<'static, str>>,
@@ -43,14 +131,222 @@ pub trait Forward {
         &mut self,
         key: K,
         value: V,
-    );
-    fn strip_http_prefix_and_set_upstream<K: Into<Cow<'static, str>>, V: Into<Cow<'static, str>>>(
+    ) {
+        let key: Cow<'static, str> = key.into();
+        if let Some(key) = key.strip_prefix(crate::HTTP_PREFIX_PERSISTENT) {
+            self.set_persistent(crate::intern::lookup(key), value);
+        }
+    }
+    fn strip_http_prefix_and_set_upstream<
+        K: Into<Cow<'static, str>>,
+        V: Into<Cow<'static, str>>,
+    >(
         &mut self,
         key: K,
         value: V,
-    );
+    ) {
+        let key: Cow<'static, str> = key.into();
+        if let Some(key) = key.strip_prefix(crate::HTTP_PREFIX_TRANSIENT) {
+            self.set_upstream(crate::intern::lookup(key), value);
+        }
+    }
 
     fn del_persistent<K: AsRef<str>>(&mut self, key: K);
     fn del_transient<K: AsRef<str>>(&mut self, key: K);
     fn del_upstream<K: AsRef<str>>(&mut self, key: K);
+
+    #[cfg(feature = "bytes")]
+    fn set_persistent_bytes<K: Into<Cow<'static, str>>, V: Into<Bytes>>(
+        &mut self,
+        key: K,
+        value: V,
+    );
+    #[cfg(feature = "bytes")]
+    fn set_transient_bytes<K: Into<Cow<'static, str>>, V: Into<Bytes>>(&mut self, key: K, value: V);
+    #[cfg(feature = "bytes")]
+    fn set_upstream_bytes<K: Into<Cow<'static, str>>, V: Into<Bytes>>(&mut self, key: K, value: V);
+
+    #[cfg(feature = "bytes")]
+    fn del_persistent_bytes<K: AsRef<str>>(&mut self, key: K);
+    #[cfg(feature = "bytes")]
+    fn del_transient_bytes<K: AsRef<str>>(&mut self, key: K);
+    #[cfg(feature = "bytes")]
+    fn del_upstream_bytes<K: AsRef<str>>(&mut self, key: K);
+}
+
+/// The object-safe version of [`Forward`], implemented for every `Forward`.
+///
+/// The methods are those of `Forward` prefixed by `dyn_`, so both traits can be
+/// in scope together.
+///
+/// ```rust
+/// use metainfo::forward::DynForward;
+/// use metainfo::kv::Node;
+/// use metainfo::MetaInfo;
+///
+/// let mut carriers: Vec<Box<dyn DynForward>> =
+///     vec![Box::new(MetaInfo::new()), Box::new(Node::default())];
+/// for carrier in carriers.iter_mut() {
+///     carrier.dyn_set_persistent("tenant".into(), "t1".into());
+///     assert_eq!(carrier.dyn_get_persistent("tenant"), Some("t1"));
+/// }
+/// ```
+pub trait DynForward {
+    fn dyn_get_persistent(&self, key: &str) -> Option<&str>;
+    fn dyn_get_transient(&self, key: &str) -> Option<&str>;
+    fn dyn_get_upstream(&self, key: &str) -> Option<&str>;
+
+    fn dyn_persistents(&self) -> Entries<'_>;
+    fn dyn_transients(&self) -> Entries<'_>;
+    fn dyn_upstreams(&self) -> Entries<'_>;
+
+    fn dyn_set_persistent(&mut self, key: Cow<'static, str>, value: Cow<'static, str>);
+    fn dyn_set_transient(&mut self, key: Cow<'static, str>, value: Cow<'static, str>);
+    fn dyn_set_upstream(&mut self, key: Cow<'static, str>, value: Cow<'static, str>);
+
+    fn dyn_strip_rpc_prefix_and_set_persistent(
+        &mut self,
+        key: Cow<'static, str>,
+        value: Cow<'static, str>,
+    );
+    fn dyn_strip_rpc_prefix_and_set_upstream(
+        &mut self,
+        key: Cow<'static, str>,
+        value: Cow<'static, str>,
+    );
+
+    fn dyn_strip_http_prefix_and_set_persistent(
+        &mut self,
+        key: Cow<'static, str>,
+        value: Cow<'static, str>,
+    );
+    fn dyn_strip_http_prefix_and_set_upstream(
+        &mut self,
+        key: Cow<'static, str>,
+        value: Cow<'static, str>,
+    );
+
+    fn dyn_del_persistent(&mut self, key: &str);
+    fn dyn_del_transient(&mut self, key: &str);
+    fn dyn_del_upstream(&mut self, key: &str);
+
+    #[cfg(feature = "bytes")]
+    fn dyn_get_persistent_bytes(&self, key: &str) -> Option<&Bytes>;
+    #[cfg(feature = "bytes")]
+    fn dyn_get_transient_bytes(&self, key: &str) -> Option<&Bytes>;
+    #[cfg(feature = "bytes")]
+    fn dyn_get_upstream_bytes(&self, key: &str) -> Option<&Bytes>;
+
+    #[cfg(feature = "bytes")]
+    fn dyn_persistent_bytes(&self) -> BytesEntries<'_>;
+    #[cfg(feature = "bytes")]
+    fn dyn_transient_bytes(&self) -> BytesEntries<'_>;
+    #[cfg(feature = "bytes")]
+    fn dyn_upstream_bytes(&self) -> BytesEntries<'_>;
+
+    #[cfg(feature = "bytes")]
+    fn dyn_set_persistent_bytes(&mut self, key: Cow<'static, str>, value: Bytes);
+    #[cfg(feature = "bytes")]
+    fn dyn_set_transient_bytes(&mut self, key: Cow<'static, str>, value: Bytes);
+    #[cfg(feature = "bytes")]
+    fn dyn_set_upstream_bytes(&mut self, key: Cow<'static, str>, value: Bytes);
+
+    #[cfg(feature = "bytes")]
+    fn dyn_del_persistent_bytes(&mut self, key: &str);
+    #[cfg(feature = "bytes")]
+    fn dyn_del_transient_bytes(&mut self, key: &str);
+    #[cfg(feature = "bytes")]
+    fn dyn_del_upstream_bytes(&mut self, key: &str);
+}
+
+macro_rules! dyn_impl {
+    ($($name:ident),*) => {
+        paste! {
+            $(
+                #[inline]
+                fn [<dyn_get_ $name>](&self, key: &str) -> Option<&str> {
+                    ForwardRead::[<get_ $name>](self, key)
+                }
+
+                #[inline]
+                fn [<dyn_ $name s>](&self) -> Entries<'_> {
+                    ForwardRead::[<$name s>](self)
+                }
+
+                #[inline]
+                fn [<dyn_set_ $name>](&mut self, key: Cow<'static, str>, value: Cow<'static, str>) {
+                    ForwardWrite::[<set_ $name>](self, key, value)
+                }
+
+                #[inline]
+                fn [<dyn_del_ $name>](&mut self, key: &str) {
+                    ForwardWrite::[<del_ $name>](self, key)
+                }
+
+                #[cfg(feature = "bytes")]
+                #[inline]
+                fn [<dyn_get_ $name _bytes>](&self, key: &str) -> Option<&Bytes> {
+                    ForwardRead::[<get_ $name _bytes>](self, key)
+                }
+
+                #[cfg(feature = "bytes")]
+                #[inline]
+                fn [<dyn_ $name _bytes>](&self) -> BytesEntries<'_> {
+                    ForwardRead::[<$name _bytes>](self)
+                }
+
+                #[cfg(feature = "bytes")]
+                #[inline]
+                fn [<dyn_set_ $name _bytes>](&mut self, key: Cow<'static, str>, value: Bytes) {
+                    ForwardWrite::[<set_ $name _bytes>](self, key, value)
+                }
+
+                #[cfg(feature = "bytes")]
+                #[inline]
+                fn [<dyn_del_ $name _bytes>](&mut self, key: &str) {
+                    ForwardWrite::[<del_ $name _bytes>](self, key)
+                }
+            )*
+        }
+    };
+}
+
+impl<T: Forward> DynForward for T {
+    dyn_impl!(persistent, transient, upstream);
+
+    #[inline]
+    fn dyn_strip_rpc_prefix_and_set_persistent(
+        &mut self,
+        key: Cow<'static, str>,
+        value: Cow<'static, str>,
+    ) {
+        ForwardWrite::strip_rpc_prefix_and_set_persistent(self, key, value)
+    }
+
+    #[inline]
+    fn dyn_strip_rpc_prefix_and_set_upstream(
+        &mut self,
+        key: Cow<'static, str>,
+        value: Cow<'static, str>,
+    ) {
+        ForwardWrite::strip_rpc_prefix_and_set_upstream(self, key, value)
+    }
+
+    #[inline]
+    fn dyn_strip_http_prefix_and_set_persistent(
+        &mut self,
+        key: Cow<'static, str>,
+        value: Cow<'static, str>,
+    ) {
+        ForwardWrite::strip_http_prefix_and_set_persistent(self, key, value)
+    }
+
+    #[inline]
+    fn dyn_strip_http_prefix_and_set_upstream(
+        &mut self,
+        key: Cow<'static, str>,
+        value: Cow<'static, str>,
+    ) {
+        ForwardWrite::strip_http_prefix_and_set_upstream(self, key, value)
+    }
 }
@@ -0,0 +1,177 @@
+//! Read-only `MetaInfo`.
+//!
+//! [`MetaInfo::freeze`] turns a `MetaInfo` into a [`FrozenMetaInfo`], which is
+//! cheap to clone and share, and only gives `&MetaInfo` access, so none of the
+//! setters can be called on it. It implements [`ForwardRead`] and
+//! [`BackwardRead`]. [`FrozenMetaInfo::derive`] returns a mutable child to
+//! continue with.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::{ForwardRead, ForwardWrite, MetaInfo};
+//!
+//! fn tenant<F: ForwardRead>(mi: &F) -> Option<&str> {
+//!     mi.get_persistent("tenant")
+//! }
+//!
+//! let mut mi = MetaInfo::new();
+//! mi.set_persistent("tenant", "t1");
+//! let frozen = mi.freeze();
+//! assert_eq!(tenant(&frozen), Some("t1"));
+//!
+//! let mut child = frozen.derive();
+//! child.set_persistent("tenant", "t2");
+//! assert_eq!(tenant(&frozen), Some("t1"));
+//! ```
+
+#[cfg(feature = "bytes")]
+use crate::kv::BytesEntries;
+use crate::kv::Entries;
+use crate::{BackwardRead, ForwardRead, MetaInfo};
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use paste::paste;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// An immutable, shareable `MetaInfo`.
+#[derive(Clone, Default)]
+pub struct FrozenMetaInfo(Arc<MetaInfo>);
+
+impl FrozenMetaInfo {
+    /// Returns a mutable child, which reads through to this `MetaInfo`.
+    #[inline]
+    pub fn derive(&self) -> MetaInfo {
+        MetaInfo::from(self.0.clone())
+    }
+
+    /// Returns the shared `MetaInfo`.
+    #[inline]
+    pub fn into_arc(self) -> Arc<MetaInfo> {
+        self.0
+    }
+}
+
+impl Deref for FrozenMetaInfo {
+    type Target = MetaInfo;
+
+    #[inline]
+    fn deref(&self) -> &MetaInfo {
+        &self.0
+    }
+}
+
+impl AsRef<MetaInfo> for FrozenMetaInfo {
+    #[inline]
+    fn as_ref(&self) -> &MetaInfo {
+        &self.0
+    }
+}
+
+impl From<MetaInfo> for FrozenMetaInfo {
+    #[inline]
+    fn from(mi: MetaInfo) -> Self {
+        FrozenMetaInfo(Arc::new(mi))
+    }
+}
+
+impl From<Arc<MetaInfo>> for FrozenMetaInfo {
+    #[inline]
+    fn from(mi: Arc<MetaInfo>) -> Self {
+        FrozenMetaInfo(mi)
+    }
+}
+
+impl fmt::Debug for FrozenMetaInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+macro_rules! read_impl {
+    ($($name:ident),*) => {
+        paste! {
+            $(
+                #[inline]
+                fn [<get_ $name>]<K: AsRef<str>>(&self, key: K) -> Option<&str> {
+                    self.0.[<get_ $name>](key)
+                }
+
+                #[inline]
+                fn [<$name s>](&self) -> Entries<'_> {
+                    self.0.[<$name s>]()
+                }
+
+                #[cfg(feature = "bytes")]
+                #[inline]
+                fn [<get_ $name _bytes>]<K: AsRef<str>>(&self, key: K) -> Option<&Bytes> {
+                    self.0.[<get_ $name _bytes>](key)
+                }
+
+                #[cfg(feature = "bytes")]
+                #[inline]
+                fn [<$name _bytes>](&self) -> BytesEntries<'_> {
+                    self.0.[<$name _bytes>]()
+                }
+            )*
+        }
+    };
+}
+
+impl ForwardRead for FrozenMetaInfo {
+    read_impl!(persistent, transient, upstream);
+}
+
+impl BackwardRead for FrozenMetaInfo {
+    read_impl!(backward_transient, backward_downstream);
+}
+
+impl MetaInfo {
+    /// Turns this `MetaInfo` into a read-only one.
+    #[inline]
+    pub fn freeze(self) -> FrozenMetaInfo {
+        FrozenMetaInfo(Arc::new(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BackwardRead, BackwardWrite, ForwardRead, ForwardWrite};
+
+    #[test]
+    fn test_freeze() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("p", "1");
+        mi.set_backward_transient("b", "2");
+        mi.insert_string("s".into(), "3".into());
+        mi.insert(4u8);
+
+        let frozen = mi.freeze();
+        let shared = frozen.clone();
+        assert_eq!(shared.get_persistent("p"), Some("1"));
+        assert_eq!(shared.get_backward_transient("b"), Some("2"));
+        assert_eq!(shared.get_string("s").map(|v| v.as_ref()), Some("3"));
+        assert_eq!(shared.get::<u8>(), Some(&4));
+
+        let mut child = frozen.derive();
+        child.set_persistent("p", "5");
+        child.insert(6u8);
+        assert_eq!(child.get_string("s").map(|v| v.as_ref()), Some("3"));
+        assert_eq!(frozen.get_persistent("p"), Some("1"));
+        assert_eq!(frozen.get::<u8>(), Some(&4));
+    }
+
+    #[test]
+    fn test_read_traits() {
+        fn read<T: ForwardRead + BackwardRead>(mi: &T) -> (Option<&str>, Option<&str>) {
+            (mi.get_upstream("u"), mi.get_backward_downstream("d"))
+        }
+
+        let mut mi = MetaInfo::new();
+        mi.set_upstream("u", "1");
+        mi.set_backward_downstream("d", "2");
+        assert_eq!(read(&mi.freeze()), (Some("1"), Some("2")));
+    }
+}
@@ -0,0 +1,174 @@
+//! Interning of the keys set from the wire.
+//!
+//! The decoders and `strip_*_prefix_and_set_*` turn the keys into
+//! `Cow::Borrowed(&'static str)`, so the same keys don't allocate on every
+//! request. The well-known keys can be [`register`]ed up front, and are found
+//! by [`lookup`]. The keys allowed by an allow pattern of the inbound policy,
+//! see [`InboundPolicy`](crate::policy::InboundPolicy), of at most
+//! [`MAX_KEY_LEN`] bytes are also [`intern`]ed on first sight, until the table
+//! holds [`capacity`] of them. Other keys are allocated as before, so that the
+//! keys sent by peers don't fill the table. The interned keys are never freed,
+//! the capacity and the length bound the memory they take.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::intern;
+//! use std::borrow::Cow;
+//!
+//! intern::register(&["tenant", "caller"]);
+//! let key = String::from("tenant");
+//! assert!(matches!(intern::intern(&key), Cow::Borrowed("tenant")));
+//! ```
+
+use fxhash::FxHashSet;
+use std::borrow::Cow;
+use std::sync::{OnceLock, RwLock};
+
+/// The default number of keys interned on first sight.
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// The maximum length of the keys interned on first sight, longer keys are
+/// allocated.
+pub const MAX_KEY_LEN: usize = 64;
+
+struct Interner {
+    keys: FxHashSet<&'static str>,
+    // the number of keys interned on first sight
+    interned: usize,
+    capacity: usize,
+}
+
+fn interner() -> &'static RwLock<Interner> {
+    static INTERNER: OnceLock<RwLock<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(|| {
+        RwLock::new(Interner {
+            keys: FxHashSet::default(),
+            interned: 0,
+            capacity: DEFAULT_CAPACITY,
+        })
+    })
+}
+
+/// Registers the well-known keys, which don't count against the capacity.
+pub fn register(keys: &[&'static str]) {
+    let mut interner = interner().write().unwrap_or_else(|e| e.into_inner());
+    interner.keys.extend(keys);
+}
+
+/// Sets the number of keys interned on first sight, 0 disables it.
+///
+/// The keys already interned are kept.
+pub fn set_capacity(capacity: usize) {
+    interner()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .capacity = capacity;
+}
+
+/// Returns the number of keys interned on first sight.
+pub fn capacity() -> usize {
+    interner()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .capacity
+}
+
+/// Returns the registered or interned key, or an owned copy if there is none.
+///
+/// Unlike [`intern`], the key is never added to the table.
+pub fn lookup(key: &str) -> Cow<'static, str> {
+    match interner()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .keys
+        .get(key)
+    {
+        Some(&key) => Cow::Borrowed(key),
+        None => Cow::Owned(key.to_owned()),
+    }
+}
+
+/// Returns the interned key, or an owned copy if the table is full or the key
+/// is too long.
+///
+/// The key is added to the table on first sight, so it must not come from an
+/// untrusted peer unchecked; use [`lookup`] for those.
+pub fn intern(key: &str) -> Cow<'static, str> {
+    if let Some(&key) = interner()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .keys
+        .get(key)
+    {
+        return Cow::Borrowed(key);
+    }
+    if key.len() > MAX_KEY_LEN {
+        return Cow::Owned(key.to_owned());
+    }
+    let mut interner = interner().write().unwrap_or_else(|e| e.into_inner());
+    // another thread may have interned it meanwhile
+    if let Some(&key) = interner.keys.get(key) {
+        return Cow::Borrowed(key);
+    }
+    if interner.interned >= interner.capacity {
+        return Cow::Owned(key.to_owned());
+    }
+    let key: &'static str = Box::leak(key.into());
+    interner.keys.insert(key);
+    interner.interned += 1;
+    Cow::Borrowed(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let key = String::from("test_intern");
+        let first = intern(&key);
+        assert!(matches!(first, Cow::Borrowed("test_intern")));
+        let second = intern(&key);
+        assert_eq!(first.as_ptr(), second.as_ptr());
+
+        register(&["test_intern_static"]);
+        assert!(matches!(
+            intern(&String::from("test_intern_static")),
+            Cow::Borrowed("test_intern_static")
+        ));
+
+        let unknown = String::from("test_lookup");
+        assert!(matches!(lookup(&unknown), Cow::Owned(_)));
+        assert!(matches!(lookup(&unknown), Cow::Owned(_)));
+        assert!(matches!(lookup(&key), Cow::Borrowed("test_intern")));
+
+        let long = "k".repeat(MAX_KEY_LEN + 1);
+        assert!(matches!(intern(&long), Cow::Owned(_)));
+        assert!(matches!(intern(&long[..MAX_KEY_LEN]), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_inbound() {
+        use crate::policy::{InboundPolicy, Pattern};
+        use crate::{Category, ForwardWrite, MetaInfo};
+        use std::sync::Arc;
+
+        // the keys from the wire are not interned without an allow pattern
+        let mut mi = MetaInfo::new();
+        mi.strip_rpc_prefix_and_set_persistent(String::from("RPC_PERSIST_test_wire"), "v");
+        assert!(matches!(lookup("test_wire"), Cow::Owned(_)));
+
+        let policy = InboundPolicy::new()
+            .allow(Category::Persistent, Pattern::prefix("test_allowed"))
+            .deny(Category::Upstream, Pattern::exact("test_denied"));
+        let mut mi = MetaInfo::new();
+        mi.set_inbound_policy(Arc::new(policy));
+        mi.strip_rpc_prefix_and_set_persistent(String::from("RPC_PERSIST_test_allowed"), "v");
+        mi.strip_rpc_prefix_and_set_upstream(String::from("RPC_TRANSIT_test_upstream"), "v");
+        assert!(matches!(
+            lookup("test_allowed"),
+            Cow::Borrowed("test_allowed")
+        ));
+        assert!(matches!(lookup("test_upstream"), Cow::Owned(_)));
+    }
+}
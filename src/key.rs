@@ -0,0 +1,247 @@
+//! Typed string keys.
+//!
+//! A [`Key`] binds a name and a [`Category`] to the type of its value, which is
+//! parsed by `FromStr` and formatted by `Display`.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::{Key, MetaInfo};
+//!
+//! /// The id of the authenticated user.
+//! const USER_ID: Key<u64> = Key::persistent("user_id");
+//!
+//! let mut mi = MetaInfo::new();
+//! assert_eq!(mi.get_key(&USER_ID), Ok(None));
+//!
+//! mi.set_key(&USER_ID, 42);
+//! assert_eq!(mi.get_key(&USER_ID), Ok(Some(42)));
+//!
+//! mi.set_key(&Key::<String>::persistent("user_id"), "not a number".to_owned());
+//! assert!(mi.get_key(&USER_ID).is_err());
+//! ```
+
+use crate::{Category, MetaInfo};
+use std::fmt::{self, Display};
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+/// A typed key of string metainfo.
+pub struct Key<T> {
+    name: &'static str,
+    category: Category,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Key<T> {
+    #[inline]
+    pub const fn new(name: &'static str, category: Category) -> Self {
+        Key {
+            name,
+            category,
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub const fn persistent(name: &'static str) -> Self {
+        Self::new(name, Category::Persistent)
+    }
+
+    #[inline]
+    pub const fn transient(name: &'static str) -> Self {
+        Self::new(name, Category::Transient)
+    }
+
+    #[inline]
+    pub const fn upstream(name: &'static str) -> Self {
+        Self::new(name, Category::Upstream)
+    }
+
+    #[inline]
+    pub const fn backward_transient(name: &'static str) -> Self {
+        Self::new(name, Category::BackwardTransient)
+    }
+
+    #[inline]
+    pub const fn backward_downstream(name: &'static str) -> Self {
+        Self::new(name, Category::BackwardDownstream)
+    }
+
+    #[inline]
+    pub const fn string(name: &'static str) -> Self {
+        Self::new(name, Category::String)
+    }
+
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[inline]
+    pub const fn category(&self) -> Category {
+        self.category
+    }
+}
+
+impl<T> Clone for Key<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Key<T> {}
+
+impl<T> fmt::Debug for Key<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Key")
+            .field("name", &self.name)
+            .field("category", &self.category)
+            .finish()
+    }
+}
+
+/// The value of a typed key failed to parse.
+///
+/// The value may be sensitive, so it's left out of `Display` and `Debug`, and
+/// only available by [`value`](ParseError::value).
+#[derive(Clone, PartialEq, Eq)]
+pub struct ParseError {
+    key: &'static str,
+    category: Category,
+    value: String,
+    reason: String,
+}
+
+impl ParseError {
+    pub fn new<T, E: Display>(key: &Key<T>, value: &str, err: E) -> Self {
+        ParseError {
+            key: key.name,
+            category: key.category,
+            value: value.to_owned(),
+            reason: err.to_string(),
+        }
+    }
+
+    #[inline]
+    pub fn key(&self) -> &'static str {
+        self.key
+    }
+
+    #[inline]
+    pub fn category(&self) -> Category {
+        self.category
+    }
+
+    /// Returns the value which failed to parse.
+    #[inline]
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl fmt::Debug for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParseError")
+            .field("key", &self.key)
+            .field("category", &self.category)
+            .field("reason", &self.reason)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid {} metainfo {}: {}",
+            self.category.as_str(),
+            self.key,
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl MetaInfo {
+    /// Get and parse the value of the typed key.
+    pub fn get_key<T>(&self, key: &Key<T>) -> Result<Option<T>, ParseError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.get_by_category(key.category, key.name) {
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|e| ParseError::new(key, value, e)),
+            None => Ok(None),
+        }
+    }
+
+    /// Get and parse the value of the typed key, or the default value if absent.
+    pub fn get_key_or_default<T>(&self, key: &Key<T>) -> Result<T, ParseError>
+    where
+        T: FromStr + Default,
+        T::Err: Display,
+    {
+        self.get_key(key).map(Option::unwrap_or_default)
+    }
+
+    /// Format and set the value of the typed key.
+    pub fn set_key<T: Display>(&mut self, key: &Key<T>, value: T) {
+        self.set_by_category(key.category, key.name, value.to_string());
+    }
+
+    /// Delete the typed key.
+    #[inline]
+    pub fn del_key<T>(&mut self, key: &Key<T>) {
+        self.del_by_category(key.category, key.name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BackwardRead, ForwardRead, ForwardWrite};
+
+    const RETRIES: Key<u32> = Key::transient("retries");
+    const ENV: Key<String> = Key::string("env");
+    const DEBUG: Key<bool> = Key::backward_transient("debug");
+
+    #[test]
+    fn test_typed_key() {
+        let mut mi = MetaInfo::new();
+        assert_eq!(mi.get_key_or_default(&RETRIES), Ok(0));
+
+        mi.set_key(&RETRIES, 3);
+        mi.set_key(&ENV, "prod".to_owned());
+        mi.set_key(&DEBUG, true);
+        assert_eq!(mi.get_transient("retries"), Some("3"));
+        assert_eq!(mi.get_string("env").map(|v| v.as_ref()), Some("prod"));
+        assert_eq!(mi.get_backward_transient("debug"), Some("true"));
+        assert_eq!(mi.get_key(&RETRIES), Ok(Some(3)));
+        assert_eq!(mi.get_key(&ENV), Ok(Some("prod".to_owned())));
+        assert_eq!(mi.get_key(&DEBUG), Ok(Some(true)));
+
+        mi.del_key(&DEBUG);
+        assert_eq!(mi.get_key(&DEBUG), Ok(None));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let mut mi = MetaInfo::new();
+        mi.set_transient("retries", "many");
+        let err = mi.get_key(&RETRIES).unwrap_err();
+        assert_eq!(err.key(), "retries");
+        assert_eq!(err.category(), Category::Transient);
+        assert_eq!(err.value(), "many");
+        assert_eq!(
+            err.to_string(),
+            "invalid transient metainfo retries: invalid digit found in string"
+        );
+        assert!(!format!("{:?}", err).contains("many"));
+        assert!(mi.get_key_or_default(&RETRIES).is_err());
+    }
+}
@@ -1,21 +1,60 @@
+//! The storage of the forward and backward info.
+//!
+//! A [`Node`] holds one direction: as [`Forward`](crate::Forward) it stores the
+//! persistents, transients and upstreams; as [`Backward`](crate::Backward) it
+//! stores the backward transients, and the backward downstreams in the same map
+//! as the upstreams. It can be used on its own where a whole `MetaInfo` isn't
+//! needed, e.g. in codecs and tests.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::kv::Node;
+//! use metainfo::{ForwardRead, ForwardWrite};
+//!
+//! let mut node = Node::default();
+//! node.strip_rpc_prefix_and_set_persistent("RPC_PERSIST_tenant", "t1");
+//! assert_eq!(node.get_persistent("tenant"), Some("t1"));
+//! ```
+
 use paste::paste;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::fmt;
+
+use crate::backward::{BackwardRead, BackwardWrite};
+use crate::forward::{ForwardRead, ForwardWrite};
+#[cfg(feature = "bytes")]
+pub use crate::small_map::BytesEntries;
+pub use crate::small_map::Entries;
+pub(crate) use crate::small_map::SmallMap;
+use crate::{redact, Budget};
 
-const DEFAULT_CAPACITY: usize = 10; // maybe enough for most cases?
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
 
 macro_rules! set_impl {
     ($name:ident) => {
         paste! {
-            pub fn [<set_ $name>]<K: Into<Cow<'static, str>>, V: Into<Cow<'static, str>>>(
+            pub(crate) fn [<set_ $name>]<K: Into<Cow<'static, str>>, V: Into<Cow<'static, str>>>(
                 &mut self,
                 key: K,
                 value: V,
-            ) {
-                if self.$name.is_none() {
-                    self.$name = Some(HashMap::with_capacity(DEFAULT_CAPACITY));
+            ) -> Option<Cow<'static, str>> {
+                let key = key.into();
+                if let Some(v) = self.budgets.as_mut() {
+                    v.$name.remove(&key);
                 }
-                self.$name.as_mut().unwrap().insert(key.into(), value.into());
+                #[cfg(feature = "multi_value")]
+                if let Some(v) = self.[<$name _extra>].as_mut() {
+                    v.remove(&key);
+                }
+                // a key holds either a text or a bytes value
+                #[cfg(feature = "bytes")]
+                if let Some(v) = self.[<$name _bytes>].as_mut() {
+                    v.remove(&key);
+                }
+                self.$name
+                    .get_or_insert_with(Default::default)
+                    .insert(key, value.into())
             }
         }
     };
@@ -24,11 +63,23 @@ macro_rules! set_impl {
 macro_rules! del_impl {
     ($name:ident) => {
         paste! {
-            pub fn [<del_ $name>]<K: AsRef<str>>(&mut self, key: K) {
+            pub(crate) fn [<del_ $name>]<K: AsRef<str>>(&mut self, key: K) -> Option<Cow<'static, str>> {
                 let key = key.as_ref();
-                if let Some(v) = self.$name.as_mut() {
+                if let Some(v) = self.budgets.as_mut() {
+                    v.$name.remove(key);
+                }
+                #[cfg(feature = "multi_value")]
+                if let Some(v) = self.[<$name _extra>].as_mut() {
+                    v.remove(key);
+                }
+                #[cfg(feature = "bytes")]
+                if let Some(v) = self.[<$name _bytes>].as_mut() {
                     v.remove(key);
                 }
+                match self.$name.as_mut() {
+                    Some(v) => v.remove(key),
+                    None => None,
+                }
             }
         }
     };
@@ -37,7 +88,7 @@ macro_rules! del_impl {
 macro_rules! get_impl {
     ($name:ident) => {
         paste! {
-            pub fn [<get_ $name>]<K: AsRef<str>>(&self, key: K) -> Option<&str> {
+            pub(crate) fn [<get_ $name>]<K: AsRef<str>>(&self, key: K) -> Option<&str> {
                 let key = key.as_ref();
                 match self.$name.as_ref() {
                     Some(v) => {
@@ -50,22 +101,164 @@ macro_rules! get_impl {
     };
 }
 
+macro_rules! budget_impl {
+    ($name:ident) => {
+        paste! {
+            /// Sets the budget of the key, which is dropped with the value.
+            pub(crate) fn [<set_ $name _budget>]<K: Into<Cow<'static, str>>>(&mut self, key: K, budget: Budget) {
+                self.budgets
+                    .get_or_insert_with(Default::default)
+                    .$name
+                    .insert(key.into(), budget);
+            }
+
+            pub(crate) fn [<get_ $name _budget>]<K: AsRef<str>>(&self, key: K) -> Option<&Budget> {
+                match self.budgets.as_ref() {
+                    Some(v) => v.$name.get(key.as_ref()),
+                    None => None,
+                }
+            }
+        }
+    };
+}
+
 macro_rules! get_all_impl {
     ($name:ident) => {
         paste! {
-            pub fn [<get_all_ $name s>](&self) -> Option<&HashMap<Cow<'static, str>, Cow<'static, str>>> {
-                self.$name.as_ref()
+            pub(crate) fn [<get_all_ $name s>](&self) -> Option<&SmallMap<Cow<'static, str>>> {
+                self.$name.as_deref()
+            }
+        }
+    };
+}
+
+#[cfg(feature = "bytes")]
+macro_rules! bytes_impl {
+    ($name:ident) => {
+        paste! {
+            pub(crate) fn [<set_ $name _bytes>]<K: Into<Cow<'static, str>>, V: Into<Bytes>>(
+                &mut self,
+                key: K,
+                value: V,
+            ) -> Option<Bytes> {
+                let key = key.into();
+                if let Some(v) = self.$name.as_mut() {
+                    v.remove(&key);
+                }
+                if let Some(v) = self.budgets.as_mut() {
+                    v.$name.remove(&key);
+                }
+                #[cfg(feature = "multi_value")]
+                if let Some(v) = self.[<$name _extra>].as_mut() {
+                    v.remove(&key);
+                }
+                self.[<$name _bytes>]
+                    .get_or_insert_with(Default::default)
+                    .insert(key, value.into())
+            }
+
+            pub(crate) fn [<del_ $name _bytes>]<K: AsRef<str>>(&mut self, key: K) -> Option<Bytes> {
+                match self.[<$name _bytes>].as_mut() {
+                    Some(v) => v.remove(key.as_ref()),
+                    None => None,
+                }
+            }
+
+            pub(crate) fn [<get_ $name _bytes>]<K: AsRef<str>>(&self, key: K) -> Option<&Bytes> {
+                match self.[<$name _bytes>].as_ref() {
+                    Some(v) => v.get(key.as_ref()),
+                    None => None,
+                }
+            }
+
+            pub(crate) fn [<get_all_ $name _bytes>](&self) -> Option<&SmallMap<Bytes>> {
+                self.[<$name _bytes>].as_deref()
+            }
+        }
+    };
+}
+
+#[cfg(feature = "multi_value")]
+macro_rules! multi_impl {
+    ($name:ident) => {
+        paste! {
+            /// Appends a value to the key, sets it if the key is absent.
+            pub(crate) fn [<append_ $name>]<K: Into<Cow<'static, str>>, V: Into<Cow<'static, str>>>(
+                &mut self,
+                key: K,
+                value: V,
+            ) {
+                let key = key.into();
+                if self.[<get_ $name>](&key).is_none() {
+                    self.[<set_ $name>](key, value);
+                    return;
+                }
+                self.[<$name _extra>]
+                    .get_or_insert_with(Default::default)
+                    .get_or_insert_default(key)
+                    .push(value.into());
+            }
+
+            /// Returns all the values of the key, in the order they are set.
+            pub(crate) fn [<get_ $name _values>]<K: AsRef<str>>(&self, key: K) -> Vec<&str> {
+                let key = key.as_ref();
+                let first = self.[<get_ $name>](key);
+                let extra = self.[<$name _extra>].as_ref().and_then(|v| v.get(key));
+                first
+                    .into_iter()
+                    .chain(extra.into_iter().flatten().map(|v| v.as_ref()))
+                    .collect()
+            }
+
+            /// Returns the values after the first one of the keys with several values.
+            pub(crate) fn [<get_all_ $name _extras>](
+                &self,
+            ) -> Option<&SmallMap<Vec<Cow<'static, str>>>> {
+                self.[<$name _extra>].as_deref()
             }
         }
     };
 }
 
+// the maps are boxed to keep an empty one a word, as most of them are empty
+type Map<V> = Option<Box<SmallMap<V>>>;
+
+/// The budgets of the keys of each map of a [`Node`].
 #[derive(Debug, Default, Clone)]
+struct Budgets {
+    persistent: SmallMap<Budget>,
+    transient: SmallMap<Budget>,
+    stale: SmallMap<Budget>,
+}
+
+/// The storage of the string k-v of one direction.
+///
+/// Its `Debug` redacts the values of the keys marked sensitive by
+/// [`redact::mark_sensitive`](crate::redact::mark_sensitive).
+#[derive(Default, Clone)]
 pub struct Node {
-    persistent: Option<HashMap<Cow<'static, str>, Cow<'static, str>>>,
-    transient: Option<HashMap<Cow<'static, str>, Cow<'static, str>>>,
+    persistent: Map<Cow<'static, str>>,
+    transient: Map<Cow<'static, str>>,
     // this is called stale because upstream and downstream all use this.
-    stale: Option<HashMap<Cow<'static, str>, Cow<'static, str>>>,
+    stale: Map<Cow<'static, str>>,
+
+    // boxed in one piece, as few keys ever have a budget
+    budgets: Option<Box<Budgets>>,
+
+    #[cfg(feature = "bytes")]
+    persistent_bytes: Map<Bytes>,
+    #[cfg(feature = "bytes")]
+    transient_bytes: Map<Bytes>,
+    #[cfg(feature = "bytes")]
+    stale_bytes: Map<Bytes>,
+
+    // the values after the first one, which is in the maps above
+    #[cfg(feature = "multi_value")]
+    persistent_extra: Map<Vec<Cow<'static, str>>>,
+    #[cfg(feature = "multi_value")]
+    transient_extra: Map<Vec<Cow<'static, str>>>,
+    #[cfg(feature = "multi_value")]
+    stale_extra: Map<Vec<Cow<'static, str>>>,
 }
 
 impl Node {
@@ -85,36 +278,301 @@ impl Node {
     get_all_impl!(transient);
     get_all_impl!(stale);
 
-    pub fn extend(&mut self, other: Self) {
+    budget_impl!(persistent);
+    budget_impl!(transient);
+    budget_impl!(stale);
+
+    #[cfg(feature = "bytes")]
+    bytes_impl!(persistent);
+    #[cfg(feature = "bytes")]
+    bytes_impl!(transient);
+    #[cfg(feature = "bytes")]
+    bytes_impl!(stale);
+
+    #[cfg(feature = "multi_value")]
+    multi_impl!(persistent);
+    #[cfg(feature = "multi_value")]
+    multi_impl!(transient);
+    #[cfg(feature = "multi_value")]
+    multi_impl!(stale);
+
+    /// Extends self with the k-v of another node, replacing the same keys.
+    pub fn extend(&mut self, mut other: Self) {
+        // a key holds either a text or a bytes value, the one of other wins
+        #[cfg(feature = "bytes")]
+        {
+            remove_keys(&mut self.persistent_bytes, &other.persistent);
+            remove_keys(&mut self.transient_bytes, &other.transient);
+            remove_keys(&mut self.stale_bytes, &other.stale);
+
+            remove_keys(&mut self.persistent, &other.persistent_bytes);
+            remove_keys(&mut self.transient, &other.transient_bytes);
+            remove_keys(&mut self.stale, &other.stale_bytes);
+            if let Some(this) = self.budgets.as_mut() {
+                for (budgets, values) in [
+                    (&mut this.persistent, &other.persistent_bytes),
+                    (&mut this.transient, &other.transient_bytes),
+                    (&mut this.stale, &other.stale_bytes),
+                ] {
+                    for key in values.iter().flat_map(|v| v.keys()) {
+                        budgets.remove(key);
+                    }
+                }
+            }
+            #[cfg(feature = "multi_value")]
+            {
+                remove_keys(&mut self.persistent_extra, &other.persistent_bytes);
+                remove_keys(&mut self.transient_extra, &other.transient_bytes);
+                remove_keys(&mut self.stale_extra, &other.stale_bytes);
+            }
+        }
+
+        // the budgets go with the values
+        if let Some(this) = self.budgets.as_mut() {
+            for (budgets, values) in [
+                (&mut this.persistent, &other.persistent),
+                (&mut this.transient, &other.transient),
+                (&mut this.stale, &other.stale),
+            ] {
+                for key in values.iter().flat_map(|v| v.keys()) {
+                    budgets.remove(key);
+                }
+            }
+        }
+        if let Some(budgets) = other.budgets.take() {
+            match self.budgets.as_mut() {
+                Some(this) => {
+                    this.persistent.extend(budgets.persistent);
+                    this.transient.extend(budgets.transient);
+                    this.stale.extend(budgets.stale);
+                }
+                None => self.budgets = Some(budgets),
+            }
+        }
+
+        // the values of other replace all the values of the same keys
+        #[cfg(feature = "multi_value")]
+        for (this, values, extra) in [
+            (
+                &mut self.persistent_extra,
+                &other.persistent,
+                other.persistent_extra.take(),
+            ),
+            (
+                &mut self.transient_extra,
+                &other.transient,
+                other.transient_extra.take(),
+            ),
+            (
+                &mut self.stale_extra,
+                &other.stale,
+                other.stale_extra.take(),
+            ),
+        ] {
+            if let Some(this) = this.as_mut() {
+                for key in values.iter().flat_map(|v| v.keys()) {
+                    this.remove(key);
+                }
+            }
+            if let Some(extra) = extra {
+                this.get_or_insert_with(Default::default).extend(*extra);
+            }
+        }
+
         if let Some(v) = other.persistent {
-            if self.persistent.is_none() {
-                self.persistent = Some(v);
-            } else {
-                self.persistent.as_mut().unwrap().extend(v);
+            match self.persistent.as_mut() {
+                Some(persistent) => persistent.extend(*v),
+                None => self.persistent = Some(v),
             }
         }
 
         if let Some(v) = other.transient {
-            if self.transient.is_none() {
-                self.transient = Some(v);
-            } else {
-                self.transient.as_mut().unwrap().extend(v);
+            match self.transient.as_mut() {
+                Some(transient) => transient.extend(*v),
+                None => self.transient = Some(v),
             }
         }
 
         if let Some(v) = other.stale {
-            if self.stale.is_none() {
-                self.stale = Some(v);
-            } else {
-                self.stale.as_mut().unwrap().extend(v);
+            match self.stale.as_mut() {
+                Some(stale) => stale.extend(*v),
+                None => self.stale = Some(v),
+            }
+        }
+
+        #[cfg(feature = "bytes")]
+        for (this, other) in [
+            (&mut self.persistent_bytes, other.persistent_bytes),
+            (&mut self.transient_bytes, other.transient_bytes),
+            (&mut self.stale_bytes, other.stale_bytes),
+        ] {
+            if let Some(v) = other {
+                match this.as_mut() {
+                    Some(this) => this.extend(*v),
+                    None => *this = Some(v),
+                }
             }
         }
     }
 }
 
+impl fmt::Debug for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("Node");
+        for (name, map) in [
+            ("persistent", &self.persistent),
+            ("transient", &self.transient),
+            ("stale", &self.stale),
+        ] {
+            if let Some(map) = map {
+                d.field(name, &Redacted(map));
+            }
+        }
+        #[cfg(feature = "bytes")]
+        for (name, map) in [
+            ("persistent_bytes", &self.persistent_bytes),
+            ("transient_bytes", &self.transient_bytes),
+            ("stale_bytes", &self.stale_bytes),
+        ] {
+            if let Some(map) = map {
+                d.field(name, &Redacted(map));
+            }
+        }
+        #[cfg(feature = "multi_value")]
+        for (name, map) in [
+            ("persistent_extra", &self.persistent_extra),
+            ("transient_extra", &self.transient_extra),
+            ("stale_extra", &self.stale_extra),
+        ] {
+            if let Some(map) = map {
+                d.field(name, &Redacted(map));
+            }
+        }
+        if let Some(budgets) = &self.budgets {
+            d.field("budgets", budgets);
+        }
+        d.finish()
+    }
+}
+
+// a node has no patterns of its own, only the global ones apply
+struct Redacted<'a, V>(&'a SmallMap<V>);
+
+impl<V: fmt::Debug> fmt::Debug for Redacted<'_, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(k, v)| {
+                let v: &dyn fmt::Debug = if redact::is_sensitive(k) {
+                    &redact::REDACTED
+                } else {
+                    v
+                };
+                (k, v)
+            }))
+            .finish()
+    }
+}
+
+/// Removes the keys of `keys` from `map`.
+#[cfg(feature = "bytes")]
+fn remove_keys<V, W>(map: &mut Map<V>, keys: &Map<W>) {
+    if let (Some(map), Some(keys)) = (map.as_mut(), keys.as_ref()) {
+        for key in keys.keys() {
+            map.remove(key);
+        }
+    }
+}
+
+macro_rules! trait_get_impl {
+    ($name:ident,$func_name:ident) => {
+        paste! {
+            #[inline]
+            fn [<get_ $name>]<K: AsRef<str>>(&self, key: K) -> Option<&str> {
+                Node::[<get_ $func_name>](self, key)
+            }
+
+            #[inline]
+            fn [<$name s>](&self) -> Entries<'_> {
+                Entries::new(Node::[<get_all_ $func_name s>](self))
+            }
+
+            #[cfg(feature = "bytes")]
+            #[inline]
+            fn [<get_ $name _bytes>]<K: AsRef<str>>(&self, key: K) -> Option<&Bytes> {
+                Node::[<get_ $func_name _bytes>](self, key)
+            }
+
+            #[cfg(feature = "bytes")]
+            #[inline]
+            fn [<$name _bytes>](&self) -> BytesEntries<'_> {
+                BytesEntries::new(Node::[<get_all_ $func_name _bytes>](self))
+            }
+        }
+    };
+}
+
+macro_rules! trait_set_impl {
+    ($name:ident,$func_name:ident) => {
+        paste! {
+            #[inline]
+            fn [<set_ $name>]<K: Into<Cow<'static, str>>, V: Into<Cow<'static, str>>>(
+                &mut self,
+                key: K,
+                value: V,
+            ) {
+                Node::[<set_ $func_name>](self, key, value);
+            }
+
+            #[inline]
+            fn [<del_ $name>]<K: AsRef<str>>(&mut self, key: K) {
+                Node::[<del_ $func_name>](self, key);
+            }
+
+            #[cfg(feature = "bytes")]
+            #[inline]
+            fn [<set_ $name _bytes>]<K: Into<Cow<'static, str>>, V: Into<Bytes>>(
+                &mut self,
+                key: K,
+                value: V,
+            ) {
+                Node::[<set_ $func_name _bytes>](self, key, value);
+            }
+
+            #[cfg(feature = "bytes")]
+            #[inline]
+            fn [<del_ $name _bytes>]<K: AsRef<str>>(&mut self, key: K) {
+                Node::[<del_ $func_name _bytes>](self, key);
+            }
+        }
+    };
+}
+
+impl ForwardRead for Node {
+    trait_get_impl!(persistent, persistent);
+    trait_get_impl!(transient, transient);
+    trait_get_impl!(upstream, stale);
+}
+
+impl ForwardWrite for Node {
+    trait_set_impl!(persistent, persistent);
+    trait_set_impl!(transient, transient);
+    trait_set_impl!(upstream, stale);
+}
+
+impl BackwardRead for Node {
+    trait_get_impl!(backward_transient, transient);
+    trait_get_impl!(backward_downstream, stale);
+}
+
+impl BackwardWrite for Node {
+    trait_set_impl!(backward_transient, transient);
+    trait_set_impl!(backward_downstream, stale);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{Backward, Forward};
 
     #[test]
     fn test_add_stale() {
@@ -122,4 +580,105 @@ mod tests {
         node.set_stale("key", "value");
         println!("{:?}", node);
     }
+
+    #[test]
+    fn test_debug_redacted() {
+        crate::redact::mark_sensitive(crate::policy::Pattern::exact("test_node_secret"));
+        let mut node = Node::default();
+        node.set_persistent("test_node_secret", "s3cr3t");
+        node.set_persistent("tenant", "t1");
+        let debug = format!("{:?}", node);
+        assert!(!debug.contains("s3cr3t"));
+        assert!(debug.contains(crate::redact::REDACTED));
+        assert!(debug.contains("t1"));
+    }
+
+    #[test]
+    fn test_traits() {
+        fn copy<F: Forward, B: Backward>(from: &F, to: &mut B) {
+            #[allow(deprecated)]
+            let all = from.get_all_persistents().unwrap_or_default();
+            assert_eq!(all.len(), from.persistents().len());
+            for (k, v) in from.persistents() {
+                to.set_backward_transient(k.to_owned(), v.to_owned());
+            }
+        }
+
+        let mut node = Node::default();
+        node.strip_http_prefix_and_set_persistent("rpc-persist-k", "v");
+        node.strip_http_prefix_and_set_persistent("other", "v");
+        assert_eq!(ForwardRead::get_persistent(&node, "k"), Some("v"));
+        assert_eq!(node.persistents().len(), 1);
+        assert!(node.upstreams().is_empty());
+
+        let mut mi = crate::MetaInfo::new();
+        copy(&node, &mut mi);
+        assert_eq!(mi.get_backward_transient("k"), Some("v"));
+        #[allow(deprecated)]
+        let all = mi.get_all_backward_transients().unwrap();
+        assert_eq!(all.get("k").map(|v| v.as_ref()), Some("v"));
+
+        ForwardWrite::set_upstream(&mut node, "u", "1");
+        assert_eq!(node.get_backward_downstream("u"), Some("1"));
+        ForwardWrite::del_persistent(&mut node, "k");
+        assert_eq!(ForwardRead::get_persistent(&node, "k"), None);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_bytes() {
+        let mut node = Node::default();
+        node.set_persistent("key", "value");
+        node.set_persistent_bytes("key", Bytes::from_static(b"\xff"));
+        assert_eq!(node.get_persistent("key"), None);
+        assert_eq!(node.get_persistent_bytes("key").unwrap()[..], [0xff]);
+
+        node.set_persistent("key", "value");
+        assert_eq!(node.get_persistent_bytes("key"), None);
+        assert_eq!(node.get_persistent("key"), Some("value"));
+
+        node.set_transient_bytes("key", Bytes::from_static(b"\xff"));
+        node.del_transient("key");
+        assert_eq!(node.get_transient_bytes("key"), None);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_extend_bytes() {
+        let mut node = Node::default();
+        node.set_persistent("text", "value");
+        node.set_persistent_budget("text", Budget::hops(1));
+        node.set_persistent_bytes("bytes", Bytes::from_static(b"\xff"));
+
+        let mut other = Node::default();
+        other.set_persistent_bytes("text", Bytes::from_static(b"\x01"));
+        other.set_persistent("bytes", "value");
+        node.extend(other);
+
+        assert_eq!(node.get_persistent("text"), None);
+        assert_eq!(node.get_persistent_budget("text"), None);
+        assert_eq!(node.get_persistent_bytes("text").unwrap()[..], [0x01]);
+        assert_eq!(node.get_persistent_bytes("bytes"), None);
+        assert_eq!(node.get_persistent("bytes"), Some("value"));
+    }
+
+    #[cfg(feature = "multi_value")]
+    #[test]
+    fn test_multi_value() {
+        let mut node = Node::default();
+        node.append_transient("hops", "a");
+        node.append_transient("hops", "b");
+        node.append_transient("hops", "c");
+        assert_eq!(node.get_transient("hops"), Some("a"));
+        assert_eq!(node.get_transient_values("hops"), ["a", "b", "c"]);
+
+        let mut other = Node::default();
+        other.set_transient("hops", "d");
+        node.extend(other);
+        assert_eq!(node.get_transient_values("hops"), ["d"]);
+
+        node.append_transient("hops", "e");
+        node.del_transient("hops");
+        assert!(node.get_transient_values("hops").is_empty());
+    }
 }
@@ -1,21 +1,85 @@
+extern crate self as metainfo;
+
+mod budget;
+mod category;
+mod diff;
+mod field;
+mod fields;
+mod frozen;
+mod key;
+#[cfg(feature = "multi_value")]
+mod multi;
+mod small_map;
 mod type_map;
-
-use fxhash::FxHashMap;
-use kv::Node;
+#[cfg(feature = "bytes")]
+mod value;
+
+pub use budget::Budget;
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+pub use category::Category;
+pub use diff::{Change, DiffEntry, DiffValue, MetaInfoDiff};
+pub use field::Field;
+pub use fields::{FieldError, MetaInfoFields};
+pub use frozen::FrozenMetaInfo;
+pub use key::{Key, ParseError};
+#[cfg(feature = "bytes")]
+use kv::BytesEntries;
+use kv::{Entries, Node, SmallMap};
+#[cfg(feature = "multi_value")]
+pub use multi::JoinRule;
+use observe::Observer;
+#[cfg(not(feature = "bytes"))]
+use observe::Value;
 use paste::paste;
+use policy::{InboundPolicy, OutboundPolicy, Pattern};
 use std::borrow::Cow;
-use std::collections::HashMap;
 use std::fmt;
 use std::sync::Arc;
 pub use type_map::TypeMap;
+#[cfg(feature = "bytes")]
+pub use value::Value;
 
 pub mod backward;
+pub mod codec;
+pub mod current;
 pub mod forward;
-
-pub use backward::Backward;
-pub use forward::Forward;
-
-mod kv;
+pub mod intern;
+pub mod kv;
+#[cfg(feature = "log")]
+pub mod log;
+pub mod observe;
+#[cfg(feature = "opentelemetry")]
+pub mod opentelemetry;
+pub mod policy;
+#[cfg(feature = "rayon")]
+pub mod rayon;
+pub mod redact;
+#[cfg(feature = "signing")]
+pub mod signing;
+#[cfg(feature = "sync")]
+pub mod sync;
+#[cfg(feature = "tracing")]
+pub mod tracing;
+
+pub use backward::{Backward, BackwardRead, BackwardWrite};
+pub use forward::{Forward, ForwardRead, ForwardWrite};
+
+/// The traits needed to read and write the forward and backward info.
+///
+/// The methods of `Forward` and `Backward` are those of their read and write
+/// halves, which have to be in scope to be called:
+/// ```rust
+/// use metainfo::prelude::*;
+///
+/// let mut mi = metainfo::MetaInfo::new();
+/// mi.set_persistent("tenant", "t1");
+/// assert_eq!(mi.get_persistent("tenant"), Some("t1"));
+/// ```
+pub mod prelude {
+    pub use crate::backward::{Backward, BackwardRead, BackwardWrite};
+    pub use crate::forward::{Forward, ForwardRead, ForwardWrite};
+}
 
 #[cfg(feature = "task_local")]
 tokio::task_local! {
@@ -29,6 +93,11 @@ pub const RPC_PREFIX_BACKWARD: &str = "RPC_BACKWARD_";
 pub const HTTP_PREFIX_PERSISTENT: &str = "rpc-persist-";
 pub const HTTP_PREFIX_TRANSIENT: &str = "rpc-transit-";
 pub const HTTP_PREFIX_BACKWARD: &str = "rpc-backward-";
+/// The budgets of the persistents and transients, see [`Budget`].
+pub const RPC_PREFIX_BUDGET_PERSISTENT: &str = "RPC_BUDGET_PERSIST_";
+pub const RPC_PREFIX_BUDGET_TRANSIENT: &str = "RPC_BUDGET_TRANSIT_";
+pub const HTTP_PREFIX_BUDGET_PERSISTENT: &str = "rpc-budget-persist-";
+pub const HTTP_PREFIX_BUDGET_TRANSIENT: &str = "rpc-budget-transit-";
 
 /// `MetaInfo` is used to passthrough information between components and even client-server.
 ///
@@ -64,12 +133,23 @@ pub struct MetaInfo {
     /// we search it in the parent scope.
     parent: Option<Arc<MetaInfo>>,
     tmap: Option<TypeMap>,
-    smap: Option<FxHashMap<Cow<'static, str>, Cow<'static, str>>>, // for str k-v
+    smap: Option<Box<SmallMap<Cow<'static, str>>>>, // for str k-v
 
     /// for information transport through client and server.
     /// e.g. RPC
     forward_node: Option<kv::Node>,
     backward_node: Option<kv::Node>,
+
+    /// Filters the keys set by `strip_*_prefix_and_set_*`, inherited by children.
+    inbound_policy: Option<Arc<InboundPolicy>>,
+    /// Filters the keys written by the codecs, inherited by children.
+    outbound_policy: Option<Arc<OutboundPolicy>>,
+    /// Keys whose values are redacted, inherited by children.
+    sensitive: Option<Arc<Vec<Pattern>>>,
+    /// Observers of the mutations, inherited by children.
+    observers: Option<Arc<Vec<Arc<dyn Observer>>>>,
+    /// Passed to the observers with the mutations of the current scope.
+    mutation_tag: Option<Cow<'static, str>>,
 }
 
 impl MetaInfo {
@@ -87,6 +167,10 @@ impl MetaInfo {
     pub fn from(parent: Arc<MetaInfo>) -> MetaInfo {
         let forward_node = parent.forward_node.clone();
         let backward_node = parent.backward_node.clone();
+        let inbound_policy = parent.inbound_policy.clone();
+        let outbound_policy = parent.outbound_policy.clone();
+        let sensitive = parent.sensitive.clone();
+        let observers = parent.observers.clone();
         MetaInfo {
             parent: Some(parent),
             tmap: None,
@@ -94,6 +178,11 @@ impl MetaInfo {
 
             forward_node,
             backward_node,
+            inbound_policy,
+            outbound_policy,
+            sensitive,
+            observers,
+            mutation_tag: None,
         }
     }
 
@@ -112,6 +201,11 @@ impl MetaInfo {
                 smap: None,
                 forward_node: self.forward_node.clone(),
                 backward_node: self.backward_node.clone(),
+                inbound_policy: self.inbound_policy.clone(),
+                outbound_policy: self.outbound_policy.clone(),
+                sensitive: self.sensitive.clone(),
+                observers: self.observers.clone(),
+                mutation_tag: None,
             };
             (self, new)
         } else {
@@ -120,6 +214,27 @@ impl MetaInfo {
         }
     }
 
+    /// Returns a child equivalent to this one, without re-parenting this one as
+    /// [`derive`](MetaInfo::derive) does.
+    ///
+    /// The child shares the parent and copies the k-v of the current scope. The
+    /// typed values of the current scope can't be copied, so they are left out.
+    #[cfg(feature = "sync")]
+    pub(crate) fn snapshot(&self) -> MetaInfo {
+        MetaInfo {
+            parent: self.parent.clone(),
+            tmap: None,
+            smap: self.smap.clone(),
+            forward_node: self.forward_node.clone(),
+            backward_node: self.backward_node.clone(),
+            inbound_policy: self.inbound_policy.clone(),
+            outbound_policy: self.outbound_policy.clone(),
+            sensitive: self.sensitive.clone(),
+            observers: self.observers.clone(),
+            mutation_tag: None,
+        }
+    }
+
     /// Insert a type into this `MetaInfo`.
     #[inline]
     pub fn insert<T: Send + Sync + 'static>(&mut self, val: T) {
@@ -129,9 +244,22 @@ impl MetaInfo {
     /// Insert a string k-v into this `MetaInfo`.
     #[inline]
     pub fn insert_string(&mut self, key: Cow<'static, str>, val: Cow<'static, str>) {
-        self.smap
-            .get_or_insert_with(FxHashMap::default)
-            .insert(key, val);
+        if !self.is_observed() {
+            self.smap
+                .get_or_insert_with(Default::default)
+                .insert(key, val);
+            return;
+        }
+        let old = self
+            .smap
+            .get_or_insert_with(Default::default)
+            .insert(key.clone(), val.clone());
+        self.notify(
+            Category::String,
+            &key,
+            old.as_deref().map(Value::Text),
+            Some(Value::Text(&val)),
+        );
     }
 
     /// Check if `MetaInfo` contains entry
@@ -202,7 +330,11 @@ impl MetaInfo {
     /// Can only remove the type in the current scope.
     #[inline]
     pub fn remove_string(&mut self, key: &str) -> Option<Cow<'static, str>> {
-        self.smap.as_mut().and_then(|smap| smap.remove(key))
+        let old = self.smap.as_mut().and_then(|smap| smap.remove(key));
+        if let Some(old) = old.as_deref() {
+            self.notify(Category::String, key, Some(Value::Text(old)), None);
+        }
+        old
     }
 
     /// Clear the `MetaInfo` of all inserted MetaInfo.
@@ -211,8 +343,14 @@ impl MetaInfo {
         if let Some(tmap) = self.tmap.as_mut() {
             tmap.clear()
         }
-        if let Some(smap) = self.smap.as_mut() {
-            smap.clear()
+        if let Some(mut smap) = self.smap.take() {
+            if self.is_observed() {
+                for (key, old) in smap.iter() {
+                    self.notify(Category::String, key, Some(Value::Text(old)), None);
+                }
+            }
+            smap.clear();
+            self.smap = Some(smap);
         }
     }
 
@@ -220,30 +358,171 @@ impl MetaInfo {
     /// Only extend the items in the current scope.
     #[inline]
     pub fn extend(&mut self, other: MetaInfo) {
+        if self.is_observed() {
+            self.notify_extend(&other);
+        }
+
         if let Some(tmap) = other.tmap {
             self.tmap.get_or_insert_with(TypeMap::default).extend(tmap);
         }
 
         if let Some(smap) = other.smap {
-            self.smap
-                .get_or_insert_with(FxHashMap::default)
-                .extend(smap);
+            self.smap.get_or_insert_with(Default::default).extend(*smap);
         }
 
         if let Some(node) = other.forward_node {
-            if self.forward_node.is_none() {
-                self.forward_node = Some(node);
-            } else {
-                self.forward_node.as_mut().unwrap().extend(node);
+            match self.forward_node.as_mut() {
+                Some(forward_node) => forward_node.extend(node),
+                None => self.forward_node = Some(node),
             }
         }
 
         if let Some(node) = other.backward_node {
-            if self.backward_node.is_none() {
-                self.backward_node = Some(node);
+            match self.backward_node.as_mut() {
+                Some(backward_node) => backward_node.extend(node),
+                None => self.backward_node = Some(node),
+            }
+        }
+    }
+
+    /// Sets the policy which filters the keys set by `strip_*_prefix_and_set_*`.
+    ///
+    /// The policy is inherited by the derived `MetaInfo`s.
+    #[inline]
+    pub fn set_inbound_policy(&mut self, policy: Arc<InboundPolicy>) {
+        self.inbound_policy = Some(policy);
+    }
+
+    /// Sets the policy which filters the keys written by the codecs and the
+    /// OpenTelemetry propagator.
+    ///
+    /// The policy is inherited by the derived `MetaInfo`s.
+    #[inline]
+    pub fn set_outbound_policy(&mut self, policy: Arc<OutboundPolicy>) {
+        self.outbound_policy = Some(policy);
+    }
+
+    /// Get the value of the key in the given category.
+    pub fn get_by_category<K: AsRef<str>>(&self, category: Category, key: K) -> Option<&str> {
+        match category {
+            Category::Persistent => self.get_persistent(key),
+            Category::Transient => self.get_transient(key),
+            Category::Upstream => self.get_upstream(key),
+            Category::BackwardTransient => self.get_backward_transient(key),
+            Category::BackwardDownstream => self.get_backward_downstream(key),
+            Category::String => self.get_string(key.as_ref()).map(|v| v.as_ref()),
+        }
+    }
+
+    /// Set the value of the key in the given category.
+    pub fn set_by_category<K: Into<Cow<'static, str>>, V: Into<Cow<'static, str>>>(
+        &mut self,
+        category: Category,
+        key: K,
+        value: V,
+    ) {
+        match category {
+            Category::Persistent => self.set_persistent(key, value),
+            Category::Transient => self.set_transient(key, value),
+            Category::Upstream => self.set_upstream(key, value),
+            Category::BackwardTransient => self.set_backward_transient(key, value),
+            Category::BackwardDownstream => self.set_backward_downstream(key, value),
+            Category::String => self.insert_string(key.into(), value.into()),
+        }
+    }
+
+    /// Delete the key in the given category.
+    /// Can only delete the string k-v in the current scope.
+    pub fn del_by_category<K: AsRef<str>>(&mut self, category: Category, key: K) {
+        match category {
+            Category::Persistent => self.del_persistent(key),
+            Category::Transient => self.del_transient(key),
+            Category::Upstream => self.del_upstream(key),
+            Category::BackwardTransient => self.del_backward_transient(key),
+            Category::BackwardDownstream => self.del_backward_downstream(key),
+            Category::String => {
+                self.remove_string(key.as_ref());
+            }
+        }
+    }
+
+    /// Marks the keys matching the pattern as sensitive, whose values are redacted
+    /// in the debug output.
+    ///
+    /// The marks are inherited by the derived `MetaInfo`s.
+    pub fn mark_sensitive(&mut self, pattern: Pattern) {
+        Arc::make_mut(self.sensitive.get_or_insert_with(Default::default)).push(pattern);
+    }
+
+    /// Returns whether the key is marked as sensitive on this `MetaInfo` or globally.
+    pub fn is_sensitive(&self, key: &str) -> bool {
+        self.sensitive
+            .as_ref()
+            .map(|patterns| patterns.iter().any(|p| p.matches(key)))
+            .unwrap_or(false)
+            || redact::is_sensitive(key)
+    }
+
+    /// Iterates the k-v of the given category in the current scope, with the
+    /// values of sensitive keys replaced by [`redact::REDACTED`].
+    pub fn redacted(&self, category: Category) -> impl Iterator<Item = (&str, &str)> {
+        self.entries(category).map(move |(k, v)| {
+            if self.is_sensitive(k) {
+                (k, redact::REDACTED)
             } else {
-                self.backward_node.as_mut().unwrap().extend(node);
+                (k, v)
             }
+        })
+    }
+
+    /// Iterates the k-v of the given category in the current scope.
+    pub(crate) fn entries(&self, category: Category) -> Entries<'_> {
+        match category {
+            Category::Persistent => self.persistents(),
+            Category::Transient => self.transients(),
+            Category::Upstream => self.upstreams(),
+            Category::BackwardTransient => self.backward_transients(),
+            Category::BackwardDownstream => self.backward_downstreams(),
+            Category::String => Entries::new(self.smap.as_deref()),
+        }
+    }
+
+    /// Returns the keys of the given category in the current scope.
+    pub(crate) fn keys(&self, category: Category) -> Vec<String> {
+        self.entries(category).map(|(k, _)| k.to_owned()).collect()
+    }
+
+    #[inline]
+    pub(crate) fn inbound_allows(&self, category: Category, key: &str) -> bool {
+        self.inbound_policy
+            .as_ref()
+            .map(|policy| policy.allows(category, key))
+            .unwrap_or(true)
+    }
+
+    /// Returns the key received from outside as stored.
+    ///
+    /// Only the registered keys and those allowed by an allow pattern of the
+    /// inbound policy are interned, see [`intern`].
+    pub(crate) fn inbound_key(&self, category: Category, key: &str) -> Cow<'static, str> {
+        match self.inbound_policy.as_ref() {
+            Some(policy) if policy.explicitly_allows(category, key) => intern::intern(key),
+            _ => intern::lookup(key),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn outbound_allows(&self, category: Category, key: &str) -> bool {
+        self.outbound_policy
+            .as_ref()
+            .map(|policy| policy.allows(category, key))
+            .unwrap_or(true)
+    }
+
+    /// Sets the k-v received from the wire if the inbound policy allows it.
+    pub(crate) fn set_inbound(&mut self, category: Category, key: &str, value: Cow<'static, str>) {
+        if self.inbound_allows(category, key) {
+            self.set_by_category(category, self.inbound_key(category, key), value);
         }
     }
 
@@ -274,68 +553,180 @@ macro_rules! get_impl {
 }
 
 macro_rules! set_impl {
-    ($name:ident,$node:ident,$func_name:ident) => {
+    ($name:ident,$node:ident,$func_name:ident,$category:ident) => {
         paste! {
             fn [<set_ $name>]<K: Into<Cow<'static, str>>, V: Into<Cow<'static, str>>>(
                 &mut self,
                 key: K,
                 value: V,
             ) {
+                let observed = self.is_observed();
                 self.[<ensure_ $node _node>]();
-                self.[<$node _node>]
-                    .as_mut()
-                    .unwrap()
-                    .[<set_ $func_name>](key, value)
+                if !observed {
+                    let node = self.[<$node _node>].as_mut().unwrap();
+                    node.[<set_ $func_name>](key, value);
+                    return;
+                }
+                let (key, value) = (key.into(), value.into());
+                // a text value replaces the bytes one of the key
+                #[cfg(feature = "bytes")]
+                let old_bytes = self.[<get_ $name _bytes>](&key).cloned();
+                let node = self.[<$node _node>].as_mut().unwrap();
+                let old = node.[<set_ $func_name>](key.clone(), value.clone());
+                let old = old.as_deref().map(Value::Text);
+                #[cfg(feature = "bytes")]
+                let old = old.or(old_bytes.as_ref().map(Value::Bytes));
+                self.notify(Category::$category, &key, old, Some(Value::Text(&value)));
             }
         }
     };
 }
 
 macro_rules! del_impl {
-    ($name:ident,$node:ident,$func_name:ident) => {
+    ($name:ident,$node:ident,$func_name:ident,$category:ident) => {
         paste! {
             fn [<del_ $name>]<K: AsRef<str>>(&mut self, key: K) {
-                if let Some(node) = self.[<$node _node>].as_mut() {
-                    node.[<del_ $func_name>](key)
+                let key = key.as_ref();
+                #[cfg(feature = "bytes")]
+                let old_bytes = match self.is_observed() {
+                    true => self.[<get_ $name _bytes>](key).cloned(),
+                    false => None,
+                };
+                let old = match self.[<$node _node>].as_mut() {
+                    Some(node) => node.[<del_ $func_name>](key),
+                    None => None,
+                };
+                let old = old.as_deref().map(Value::Text);
+                #[cfg(feature = "bytes")]
+                let old = old.or(old_bytes.as_ref().map(Value::Bytes));
+                if old.is_some() {
+                    self.notify(Category::$category, key, old, None);
+                }
+            }
+        }
+    };
+}
+
+#[cfg(feature = "bytes")]
+macro_rules! get_bytes_impl {
+    ($name:ident,$node:ident,$func_name:ident) => {
+        paste! {
+            fn [<get_ $name _bytes>]<K: AsRef<str>>(&self, key: K) -> Option<&Bytes> {
+                match self.[<$node _node>].as_ref() {
+                    Some(node) => node.[<get_ $func_name _bytes>](key),
+                    None => None,
+                }
+            }
+
+            #[inline]
+            fn [<$name _bytes>](&self) -> BytesEntries<'_> {
+                BytesEntries::new(
+                    self.[<$node _node>]
+                        .as_ref()
+                        .and_then(|node| node.[<get_all_ $func_name _bytes>]()),
+                )
+            }
+        }
+    };
+}
+
+#[cfg(feature = "bytes")]
+macro_rules! set_bytes_impl {
+    ($name:ident,$node:ident,$func_name:ident,$category:ident) => {
+        paste! {
+            fn [<set_ $name _bytes>]<K: Into<Cow<'static, str>>, V: Into<Bytes>>(
+                &mut self,
+                key: K,
+                value: V,
+            ) {
+                let observed = self.is_observed();
+                self.[<ensure_ $node _node>]();
+                if !observed {
+                    let node = self.[<$node _node>].as_mut().unwrap();
+                    node.[<set_ $func_name _bytes>](key, value);
+                    return;
+                }
+                let (key, value) = (key.into(), value.into());
+                // a bytes value replaces the text one of the key
+                let old_text = self.[<get_ $name>](&key).map(str::to_owned);
+                let node = self.[<$node _node>].as_mut().unwrap();
+                let old = node.[<set_ $func_name _bytes>](key.clone(), value.clone());
+                let old = old
+                    .as_ref()
+                    .map(Value::Bytes)
+                    .or(old_text.as_deref().map(Value::Text));
+                self.notify(Category::$category, &key, old, Some(Value::Bytes(&value)));
+            }
+
+            fn [<del_ $name _bytes>]<K: AsRef<str>>(&mut self, key: K) {
+                let key = key.as_ref();
+                let old = match self.[<$node _node>].as_mut() {
+                    Some(node) => node.[<del_ $func_name _bytes>](key),
+                    None => None,
+                };
+                if let Some(old) = old.as_ref() {
+                    self.notify(Category::$category, key, Some(Value::Bytes(old)), None);
                 }
             }
         }
     };
 }
 
-impl forward::Forward for MetaInfo {
+impl ForwardRead for MetaInfo {
     get_impl!(persistent, forward, persistent);
     get_impl!(transient, forward, transient);
     get_impl!(upstream, forward, stale);
 
-    set_impl!(persistent, forward, persistent);
-    set_impl!(transient, forward, transient);
-    set_impl!(upstream, forward, stale);
-
-    del_impl!(persistent, forward, persistent);
-    del_impl!(transient, forward, transient);
-    del_impl!(upstream, forward, stale);
+    #[cfg(feature = "bytes")]
+    get_bytes_impl!(persistent, forward, persistent);
+    #[cfg(feature = "bytes")]
+    get_bytes_impl!(transient, forward, transient);
+    #[cfg(feature = "bytes")]
+    get_bytes_impl!(upstream, forward, stale);
 
-    fn get_all_persistents(&self) -> Option<&HashMap<Cow<'static, str>, Cow<'static, str>>> {
-        match self.forward_node.as_ref() {
-            Some(node) => node.get_all_persistents(),
-            None => None,
-        }
+    #[inline]
+    fn persistents(&self) -> Entries<'_> {
+        Entries::new(
+            self.forward_node
+                .as_ref()
+                .and_then(|node| node.get_all_persistents()),
+        )
     }
 
-    fn get_all_transients(&self) -> Option<&HashMap<Cow<'static, str>, Cow<'static, str>>> {
-        match self.forward_node.as_ref() {
-            Some(node) => node.get_all_transients(),
-            None => None,
-        }
+    #[inline]
+    fn transients(&self) -> Entries<'_> {
+        Entries::new(
+            self.forward_node
+                .as_ref()
+                .and_then(|node| node.get_all_transients()),
+        )
     }
 
-    fn get_all_upstreams(&self) -> Option<&HashMap<Cow<'static, str>, Cow<'static, str>>> {
-        match self.forward_node.as_ref() {
-            Some(node) => node.get_all_stales(),
-            None => None,
-        }
+    #[inline]
+    fn upstreams(&self) -> Entries<'_> {
+        Entries::new(
+            self.forward_node
+                .as_ref()
+                .and_then(|node| node.get_all_stales()),
+        )
     }
+}
+
+impl ForwardWrite for MetaInfo {
+    set_impl!(persistent, forward, persistent, Persistent);
+    set_impl!(transient, forward, transient, Transient);
+    set_impl!(upstream, forward, stale, Upstream);
+
+    del_impl!(persistent, forward, persistent, Persistent);
+    del_impl!(transient, forward, transient, Transient);
+    del_impl!(upstream, forward, stale, Upstream);
+
+    #[cfg(feature = "bytes")]
+    set_bytes_impl!(persistent, forward, persistent, Persistent);
+    #[cfg(feature = "bytes")]
+    set_bytes_impl!(transient, forward, transient, Transient);
+    #[cfg(feature = "bytes")]
+    set_bytes_impl!(upstream, forward, stale, Upstream);
 
     fn strip_rpc_prefix_and_set_persistent<
         K: Into<Cow<'static, str>>,
@@ -347,7 +738,9 @@ impl forward::Forward for MetaInfo {
     ) {
         let key: Cow<'static, str> = key.into();
         if let Some(key) = key.strip_prefix(crate::RPC_PREFIX_PERSISTENT) {
-            self.set_persistent(key.to_owned(), value);
+            if self.inbound_allows(Category::Persistent, key) {
+                self.set_persistent(self.inbound_key(Category::Persistent, key), value);
+            }
         }
     }
 
@@ -358,7 +751,9 @@ impl forward::Forward for MetaInfo {
     ) {
         let key: Cow<'static, str> = key.into();
         if let Some(key) = key.strip_prefix(crate::RPC_PREFIX_TRANSIENT) {
-            self.set_upstream(key.to_owned(), value);
+            if self.inbound_allows(Category::Upstream, key) {
+                self.set_upstream(self.inbound_key(Category::Upstream, key), value);
+            }
         }
     }
 
@@ -372,7 +767,9 @@ impl forward::Forward for MetaInfo {
     ) {
         let key: Cow<'static, str> = key.into();
         if let Some(key) = key.strip_prefix(crate::HTTP_PREFIX_PERSISTENT) {
-            self.set_persistent(key.to_owned(), value);
+            if self.inbound_allows(Category::Persistent, key) {
+                self.set_persistent(self.inbound_key(Category::Persistent, key), value);
+            }
         }
     }
 
@@ -386,38 +783,52 @@ impl forward::Forward for MetaInfo {
     ) {
         let key: Cow<'static, str> = key.into();
         if let Some(key) = key.strip_prefix(crate::HTTP_PREFIX_TRANSIENT) {
-            self.set_upstream(key.to_owned(), value);
+            if self.inbound_allows(Category::Upstream, key) {
+                self.set_upstream(self.inbound_key(Category::Upstream, key), value);
+            }
         }
     }
 }
 
-impl backward::Backward for MetaInfo {
+impl BackwardRead for MetaInfo {
     get_impl!(backward_transient, backward, transient);
     get_impl!(backward_downstream, backward, stale);
 
-    set_impl!(backward_transient, backward, transient);
-    set_impl!(backward_downstream, backward, stale);
+    #[cfg(feature = "bytes")]
+    get_bytes_impl!(backward_transient, backward, transient);
+    #[cfg(feature = "bytes")]
+    get_bytes_impl!(backward_downstream, backward, stale);
 
-    del_impl!(backward_transient, backward, transient);
-    del_impl!(backward_downstream, backward, stale);
-
-    fn get_all_backward_transients(
-        &self,
-    ) -> Option<&HashMap<Cow<'static, str>, Cow<'static, str>>> {
-        match self.backward_node.as_ref() {
-            Some(node) => node.get_all_transients(),
-            None => None,
-        }
+    #[inline]
+    fn backward_transients(&self) -> Entries<'_> {
+        Entries::new(
+            self.backward_node
+                .as_ref()
+                .and_then(|node| node.get_all_transients()),
+        )
     }
 
-    fn get_all_backward_downstreams(
-        &self,
-    ) -> Option<&HashMap<Cow<'static, str>, Cow<'static, str>>> {
-        match self.backward_node.as_ref() {
-            Some(node) => node.get_all_stales(),
-            None => None,
-        }
+    #[inline]
+    fn backward_downstreams(&self) -> Entries<'_> {
+        Entries::new(
+            self.backward_node
+                .as_ref()
+                .and_then(|node| node.get_all_stales()),
+        )
     }
+}
+
+impl BackwardWrite for MetaInfo {
+    set_impl!(backward_transient, backward, transient, BackwardTransient);
+    set_impl!(backward_downstream, backward, stale, BackwardDownstream);
+
+    del_impl!(backward_transient, backward, transient, BackwardTransient);
+    del_impl!(backward_downstream, backward, stale, BackwardDownstream);
+
+    #[cfg(feature = "bytes")]
+    set_bytes_impl!(backward_transient, backward, transient, BackwardTransient);
+    #[cfg(feature = "bytes")]
+    set_bytes_impl!(backward_downstream, backward, stale, BackwardDownstream);
 
     fn strip_rpc_prefix_and_set_backward_downstream<
         K: Into<Cow<'static, str>>,
@@ -429,7 +840,10 @@ impl backward::Backward for MetaInfo {
     ) {
         let key: Cow<'static, str> = key.into();
         if let Some(key) = key.strip_prefix(crate::RPC_PREFIX_BACKWARD) {
-            self.set_backward_downstream(key.to_owned(), value);
+            if self.inbound_allows(Category::BackwardDownstream, key) {
+                let key = self.inbound_key(Category::BackwardDownstream, key);
+                self.set_backward_downstream(key, value);
+            }
         }
     }
 
@@ -443,14 +857,32 @@ impl backward::Backward for MetaInfo {
     ) {
         let key: Cow<'static, str> = key.into();
         if let Some(key) = key.strip_prefix(crate::HTTP_PREFIX_BACKWARD) {
-            self.set_backward_downstream(key.to_owned(), value);
+            if self.inbound_allows(Category::BackwardDownstream, key) {
+                let key = self.inbound_key(Category::BackwardDownstream, key);
+                self.set_backward_downstream(key, value);
+            }
         }
     }
 }
 
+/// Debug output of the k-v of a category, with sensitive values redacted.
+struct Redacted<'a>(&'a MetaInfo, Category);
+
+impl fmt::Debug for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.0.redacted(self.1)).finish()
+    }
+}
+
 impl fmt::Debug for MetaInfo {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("MetaInfo").finish()
+        let mut d = f.debug_struct("MetaInfo");
+        for category in Category::ALL {
+            if self.entries(category).next().is_some() {
+                d.field(category.as_str(), &Redacted(self, category));
+            }
+        }
+        d.finish()
     }
 }
 
@@ -458,6 +890,12 @@ impl fmt::Debug for MetaInfo {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_size() {
+        // copied by every derive and scope, the maps are allocated on first use
+        assert!(std::mem::size_of::<MetaInfo>() <= 384);
+    }
+
     #[test]
     fn test_remove() {
         let mut map = MetaInfo::new();
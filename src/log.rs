@@ -0,0 +1,194 @@
+//! Integration with the `log` crate.
+//!
+//! [`KvView`] exposes selected metainfo as `log::kv::Source`, and
+//! [`MetaInfoLogger`] attaches selected metainfo of the current `MetaInfo`,
+//! see [`current`](crate::current), to every record, like a mapped diagnostic
+//! context.
+//!
+//! Examples:
+//! ```rust,no_run
+//! use metainfo::log::MetaInfoLogger;
+//! use metainfo::Field;
+//!
+//! # struct MyLogger;
+//! # impl log::Log for MyLogger {
+//! #     fn enabled(&self, _: &log::Metadata<'_>) -> bool { true }
+//! #     fn log(&self, _: &log::Record<'_>) {}
+//! #     fn flush(&self) {}
+//! # }
+//! let logger = MetaInfoLogger::new(MyLogger, vec![Field::persistent("request_id")]);
+//! log::set_logger(Box::leak(Box::new(logger))).unwrap();
+//! ```
+
+use crate::{current, Field, MetaInfo};
+use ::log::kv::{self, Key, Source, Value, VisitSource};
+use ::log::{Log, Metadata, Record};
+
+/// A view of the selected metainfo as `log::kv::Source`.
+///
+/// Sensitive values are redacted.
+pub struct KvView<'a> {
+    mi: &'a MetaInfo,
+    fields: &'a [Field],
+}
+
+impl<'a> KvView<'a> {
+    #[inline]
+    pub fn new(mi: &'a MetaInfo, fields: &'a [Field]) -> Self {
+        KvView { mi, fields }
+    }
+}
+
+impl Source for KvView<'_> {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), kv::Error> {
+        for field in self.fields {
+            if let Some(value) = field.value(self.mi) {
+                visitor.visit_pair(Key::from_str(field.name()), Value::from(value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A `log::Log` which attaches the selected metainfo of the current
+/// `MetaInfo` to every record before passing it to the inner logger.
+pub struct MetaInfoLogger<L> {
+    inner: L,
+    fields: Vec<Field>,
+}
+
+impl<L: Log> MetaInfoLogger<L> {
+    pub fn new(inner: L, fields: Vec<Field>) -> Self {
+        MetaInfoLogger { inner, fields }
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &L {
+        &self.inner
+    }
+}
+
+impl<L: Log> Log for MetaInfoLogger<L> {
+    #[inline]
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        // the metainfo may be mutably borrowed by the code which logs
+        current::with_current(|mi| match mi {
+            Some(mi) => {
+                let view = KvView::new(mi, &self.fields);
+                let kvs: [&dyn Source; 2] = [record.key_values(), &view];
+                self.inner
+                    .log(&record.to_builder().key_values(&kvs).build());
+            }
+            None => self.inner.log(record),
+        })
+    }
+
+    #[inline]
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ForwardWrite, METAINFO};
+    use std::cell::RefCell;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Collector(Mutex<Vec<String>>);
+
+    impl Log for Collector {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record<'_>) {
+            struct Visitor(String);
+
+            impl<'kvs> VisitSource<'kvs> for Visitor {
+                fn visit_pair(
+                    &mut self,
+                    key: Key<'kvs>,
+                    value: Value<'kvs>,
+                ) -> Result<(), kv::Error> {
+                    self.0.push_str(&format!(" {}={}", key, value));
+                    Ok(())
+                }
+            }
+
+            let mut visitor = Visitor(record.args().to_string());
+            record.key_values().visit(&mut visitor).unwrap();
+            self.0.lock().unwrap().push(visitor.0);
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_kv_view() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("request_id", "r1");
+        let fields = [Field::persistent("request_id"), Field::transient("missing")];
+        let view = KvView::new(&mi, &fields);
+        assert_eq!(view.count(), 1);
+        assert_eq!(
+            view.get(Key::from_str("request_id")).map(|v| v.to_string()),
+            Some("r1".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_logger() {
+        let logger = MetaInfoLogger::new(
+            Collector::default(),
+            vec![Field::persistent("request_id"), Field::string("caller")],
+        );
+        let log = |logger: &MetaInfoLogger<Collector>| {
+            logger.log(
+                &Record::builder()
+                    .args(format_args!("hello"))
+                    .key_values(&[("k", "v")])
+                    .build(),
+            )
+        };
+
+        log(&logger);
+
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("request_id", "r1");
+        mi.insert_string("caller".into(), "svc".into());
+        METAINFO.sync_scope(RefCell::new(mi), || {
+            log(&logger);
+            // e.g. logged by an observer of the mutation
+            METAINFO.with(|mi| {
+                let _mi = mi.borrow_mut();
+                log(&logger);
+            });
+        });
+
+        // e.g. in a blocking closure
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("request_id", "r2");
+        let guard = crate::current::set_current(mi);
+        log(&logger);
+        crate::current::with_current_mut(|_| log(&logger));
+        drop(guard);
+
+        assert_eq!(
+            *logger.inner().0.lock().unwrap(),
+            [
+                "hello k=v",
+                "hello k=v request_id=r1 caller=svc",
+                "hello k=v",
+                "hello k=v request_id=r2",
+                "hello k=v"
+            ]
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! Keys with several values.
+//!
+//! With the `multi_value` feature, the values appended by `append_*` are kept
+//! after the first one. The getters of the `Forward` and `Backward` traits
+//! return the first value, and [`MetaInfo::get_all_values`] returns all of them.
+//! Setting or deleting a key replaces or removes all its values.
+//!
+//! For wire formats which only allow one value per key, the values are joined
+//! into one by a [`JoinRule`], and split by the same rule on the receiver.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::{Category, ForwardRead, JoinRule, MetaInfo};
+//!
+//! let mut mi = MetaInfo::new();
+//! mi.append_persistent("hops", "edge");
+//! mi.append_persistent("hops", "gateway");
+//! assert_eq!(mi.get_persistent("hops"), Some("edge"));
+//! assert_eq!(mi.get_all_values(Category::Persistent, "hops"), ["edge", "gateway"]);
+//!
+//! let rule = JoinRule::default();
+//! let joined = mi.join_values(Category::Persistent, "hops", &rule).unwrap();
+//! assert_eq!(joined, "edge,gateway");
+//!
+//! let mut received = MetaInfo::new();
+//! received.set_split_values(Category::Upstream, "hops", &joined, &rule);
+//! assert_eq!(received.get_all_values(Category::Upstream, "hops"), ["edge", "gateway"]);
+//! ```
+
+use crate::observe::Value;
+#[cfg(feature = "bytes")]
+use crate::{BackwardRead, ForwardRead};
+use crate::{Category, MetaInfo};
+use std::borrow::Cow;
+
+/// Joins several values into one, and splits it back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JoinRule {
+    separator: char,
+    trim: bool,
+}
+
+impl JoinRule {
+    /// Joins by the separator; the split values are trimmed if `trim` is set.
+    #[inline]
+    pub const fn new(separator: char, trim: bool) -> Self {
+        JoinRule { separator, trim }
+    }
+
+    #[inline]
+    pub fn separator(&self) -> char {
+        self.separator
+    }
+
+    pub fn join<'a, I: IntoIterator<Item = &'a str>>(&self, values: I) -> String {
+        let mut joined = String::new();
+        for (i, value) in values.into_iter().enumerate() {
+            if i > 0 {
+                joined.push(self.separator);
+            }
+            joined.push_str(value);
+        }
+        joined
+    }
+
+    pub fn split<'a>(&self, value: &'a str) -> impl Iterator<Item = &'a str> {
+        let trim = self.trim;
+        value
+            .split(self.separator)
+            .map(move |v| if trim { v.trim() } else { v })
+    }
+}
+
+impl Default for JoinRule {
+    /// The comma separated list of HTTP headers, `a,b` or `a, b`.
+    fn default() -> Self {
+        JoinRule::new(',', true)
+    }
+}
+
+macro_rules! append_impl {
+    ($name:ident,$node:ident,$func_name:ident,$category:ident) => {
+        paste::paste! {
+            /// Appends a value to the key, sets it if the key is absent.
+            pub fn [<append_ $name>]<K: Into<Cow<'static, str>>, V: Into<Cow<'static, str>>>(
+                &mut self,
+                key: K,
+                value: V,
+            ) {
+                let observed = self.is_observed();
+                self.[<ensure_ $node _node>]();
+                if !observed {
+                    let node = self.[<$node _node>].as_mut().unwrap();
+                    node.[<append_ $func_name>](key, value);
+                    return;
+                }
+                let (key, value) = (key.into(), value.into());
+                // only a bytes value is replaced, a text one is appended to
+                #[cfg(feature = "bytes")]
+                let old = self.[<get_ $name _bytes>](&key).cloned();
+                let node = self.[<$node _node>].as_mut().unwrap();
+                node.[<append_ $func_name>](key.clone(), value.clone());
+                #[cfg(feature = "bytes")]
+                let old = old.as_ref().map(Value::Bytes);
+                #[cfg(not(feature = "bytes"))]
+                let old = None;
+                self.notify(Category::$category, &key, old, Some(Value::Text(&value)));
+            }
+        }
+    };
+}
+
+impl MetaInfo {
+    append_impl!(persistent, forward, persistent, Persistent);
+    append_impl!(transient, forward, transient, Transient);
+    append_impl!(upstream, forward, stale, Upstream);
+    append_impl!(backward_transient, backward, transient, BackwardTransient);
+    append_impl!(backward_downstream, backward, stale, BackwardDownstream);
+
+    /// Get all the values of the key in the given category.
+    ///
+    /// The string category holds one value per key.
+    pub fn get_all_values<K: AsRef<str>>(&self, category: Category, key: K) -> Vec<&str> {
+        let (node, key) = (self.node(category), key.as_ref());
+        match category {
+            Category::Persistent => node.map(|n| n.get_persistent_values(key)),
+            Category::Transient | Category::BackwardTransient => {
+                node.map(|n| n.get_transient_values(key))
+            }
+            Category::Upstream | Category::BackwardDownstream => {
+                node.map(|n| n.get_stale_values(key))
+            }
+            Category::String => return self.get_by_category(category, key).into_iter().collect(),
+        }
+        .unwrap_or_default()
+    }
+
+    /// Joins all the values of the key by the rule, or `None` if it's absent.
+    pub fn join_values<K: AsRef<str>>(
+        &self,
+        category: Category,
+        key: K,
+        rule: &JoinRule,
+    ) -> Option<String> {
+        let values = self.get_all_values(category, key);
+        (!values.is_empty()).then(|| rule.join(values))
+    }
+
+    /// Splits the value by the rule, and sets the values to the key.
+    pub fn set_split_values<K: Into<Cow<'static, str>>>(
+        &mut self,
+        category: Category,
+        key: K,
+        value: &str,
+        rule: &JoinRule,
+    ) {
+        let key = key.into();
+        self.del_by_category(category, &key);
+        for value in rule.split(value) {
+            let value = value.to_owned();
+            match category {
+                Category::Persistent => self.append_persistent(key.clone(), value),
+                Category::Transient => self.append_transient(key.clone(), value),
+                Category::Upstream => self.append_upstream(key.clone(), value),
+                Category::BackwardTransient => self.append_backward_transient(key.clone(), value),
+                Category::BackwardDownstream => self.append_backward_downstream(key.clone(), value),
+                Category::String => self.set_by_category(category, key.clone(), value),
+            }
+        }
+    }
+
+    /// Returns the values after the first one of the keys with several values.
+    pub(crate) fn extra_values(&self, category: Category) -> impl Iterator<Item = (&str, &str)> {
+        let node = self.node(category);
+        match category {
+            Category::Persistent => node.and_then(|n| n.get_all_persistent_extras()),
+            Category::Transient | Category::BackwardTransient => {
+                node.and_then(|n| n.get_all_transient_extras())
+            }
+            Category::Upstream | Category::BackwardDownstream => {
+                node.and_then(|n| n.get_all_stale_extras())
+            }
+            Category::String => None,
+        }
+        .into_iter()
+        .flatten()
+        .flat_map(|(k, values)| values.iter().map(move |v| (k.as_ref(), v.as_ref())))
+    }
+
+    /// Appends the value to the key in the given category if the inbound policy allows.
+    pub(crate) fn append_inbound(&mut self, category: Category, key: &str, value: &str) {
+        if !self.inbound_allows(category, key) {
+            return;
+        }
+        let (key, value) = (self.inbound_key(category, key), value.to_owned());
+        match category {
+            Category::Persistent => self.append_persistent(key, value),
+            Category::Upstream => self.append_upstream(key, value),
+            Category::BackwardDownstream => self.append_backward_downstream(key, value),
+            _ => unreachable!("only received metainfo is appended"),
+        }
+    }
+
+    fn node(&self, category: Category) -> Option<&crate::kv::Node> {
+        match category {
+            Category::Persistent | Category::Transient | Category::Upstream => {
+                self.forward_node.as_ref()
+            }
+            Category::BackwardTransient | Category::BackwardDownstream => {
+                self.backward_node.as_ref()
+            }
+            Category::String => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BackwardRead, BackwardWrite};
+
+    #[test]
+    fn test_multi_value() {
+        let mut mi = MetaInfo::new();
+        mi.append_backward_transient("flags", "a");
+        mi.append_backward_transient("flags", "b");
+        assert_eq!(mi.get_backward_transient("flags"), Some("a"));
+        assert_eq!(
+            mi.get_all_values(Category::BackwardTransient, "flags"),
+            ["a", "b"]
+        );
+
+        // the children get all the values
+        let (mut mi, child) = mi.derive();
+        assert_eq!(
+            child.get_all_values(Category::BackwardTransient, "flags"),
+            ["a", "b"]
+        );
+
+        mi.set_backward_transient("flags", "c");
+        assert_eq!(
+            mi.get_all_values(Category::BackwardTransient, "flags"),
+            ["c"]
+        );
+        assert!(mi.get_all_values(Category::Persistent, "flags").is_empty());
+    }
+
+    #[test]
+    fn test_join_rule() {
+        let rule = JoinRule::new(';', false);
+        assert_eq!(rule.join(["a", "b "]), "a;b ");
+        assert_eq!(rule.split("a; b").collect::<Vec<_>>(), ["a", " b"]);
+
+        let mut mi = MetaInfo::new();
+        mi.set_split_values(Category::Transient, "k", "x, y", &JoinRule::default());
+        assert_eq!(mi.get_all_values(Category::Transient, "k"), ["x", "y"]);
+        assert_eq!(
+            mi.join_values(Category::Transient, "k", &JoinRule::default()),
+            Some("x,y".to_owned())
+        );
+        assert_eq!(
+            mi.join_values(Category::Upstream, "k", &JoinRule::default()),
+            None
+        );
+    }
+}
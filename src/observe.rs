@@ -0,0 +1,454 @@
+//! Observers of the mutations of `MetaInfo`.
+//!
+//! An [`Observer`] added by [`MetaInfo::add_observer`] is called on every
+//! mutation of the k-v of that `MetaInfo` and of its derived children; one added
+//! by [`add_global_observer`] is called for all `MetaInfo`s. The mutation carries
+//! the tag set by [`MetaInfo::set_mutation_tag`], which is not inherited, so each
+//! scope can tell who made the change.
+//!
+//! `clear` and `extend` report each key they remove or replace. `append_*`
+//! reports the appended value as the new one, with no old value unless it
+//! replaces a bytes value. Deleting a missing key is not reported.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::observe::Mutation;
+//! use metainfo::{ForwardWrite, MetaInfo};
+//! use std::sync::{Arc, Mutex};
+//!
+//! let log = Arc::new(Mutex::new(Vec::new()));
+//! let mut mi = MetaInfo::new();
+//! let sink = log.clone();
+//! mi.add_observer(move |m: &Mutation<'_>| {
+//!     sink.lock()
+//!         .unwrap()
+//!         .push(format!("{}: {:?} -> {:?}", m.key(), m.old_value(), m.new_value()));
+//! });
+//!
+//! let (_, mut child) = mi.derive();
+//! child.set_mutation_tag("auth");
+//! child.set_persistent("user", "u1");
+//! assert_eq!(*log.lock().unwrap(), ["user: None -> Some(\"u1\")"]);
+//! ```
+
+use crate::redact::REDACTED;
+use crate::{Category, MetaInfo};
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+static GLOBAL_OBSERVERS: RwLock<Vec<Arc<dyn Observer>>> = RwLock::new(Vec::new());
+// saves the lock on the hot path when there is no global observer
+static HAS_GLOBAL_OBSERVERS: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "bytes")]
+pub(crate) use crate::Value;
+
+/// A text value, the only kind of value without the `bytes` feature.
+#[cfg(not(feature = "bytes"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Value<'a> {
+    Text(&'a str),
+}
+
+#[cfg(not(feature = "bytes"))]
+impl<'a> Value<'a> {
+    #[inline]
+    fn as_str(&self) -> Option<&'a str> {
+        match self {
+            Value::Text(text) => Some(text),
+        }
+    }
+}
+
+/// A mutation of a k-v.
+///
+/// Its `Debug` redacts the values of sensitive keys, see [`crate::redact`].
+#[derive(Clone, Copy)]
+pub struct Mutation<'a> {
+    category: Category,
+    key: &'a str,
+    old: Option<Value<'a>>,
+    new: Option<Value<'a>>,
+    tag: Option<&'a str>,
+    sensitive: bool,
+}
+
+impl<'a> Mutation<'a> {
+    #[inline]
+    pub fn category(&self) -> Category {
+        self.category
+    }
+
+    #[inline]
+    pub fn key(&self) -> &'a str {
+        self.key
+    }
+
+    /// Returns the value before the mutation, `None` if the key is added or
+    /// held a bytes value.
+    #[inline]
+    pub fn old_value(&self) -> Option<&'a str> {
+        self.old.and_then(|v| v.as_str())
+    }
+
+    /// Returns the value after the mutation, `None` if the key is deleted or
+    /// holds a bytes value.
+    #[inline]
+    pub fn new_value(&self) -> Option<&'a str> {
+        self.new.and_then(|v| v.as_str())
+    }
+
+    /// Returns the bytes value before the mutation, if the key held one.
+    #[cfg(feature = "bytes")]
+    #[inline]
+    pub fn old_bytes(&self) -> Option<&'a Bytes> {
+        match self.old {
+            Some(Value::Bytes(bytes)) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Returns the bytes value after the mutation, if the key holds one.
+    #[cfg(feature = "bytes")]
+    #[inline]
+    pub fn new_bytes(&self) -> Option<&'a Bytes> {
+        match self.new {
+            Some(Value::Bytes(bytes)) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Returns the tag of the `MetaInfo` the mutation is made on.
+    #[inline]
+    pub fn tag(&self) -> Option<&'a str> {
+        self.tag
+    }
+
+    /// Returns whether the key is sensitive for the `MetaInfo` the mutation is
+    /// made on.
+    #[inline]
+    pub fn is_sensitive(&self) -> bool {
+        self.sensitive
+    }
+}
+
+impl fmt::Debug for Mutation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("Mutation");
+        d.field("category", &self.category).field("key", &self.key);
+        if self.sensitive {
+            d.field("old", &self.old.map(|_| REDACTED))
+                .field("new", &self.new.map(|_| REDACTED));
+        } else {
+            d.field("old", &self.old).field("new", &self.new);
+        }
+        d.field("tag", &self.tag).finish()
+    }
+}
+
+/// Called on the mutations of `MetaInfo`.
+///
+/// The observer runs synchronously inside the setter, so it should be cheap and
+/// must not block.
+pub trait Observer: Send + Sync + 'static {
+    fn on_mutation(&self, mutation: &Mutation<'_>);
+}
+
+impl<F> Observer for F
+where
+    F: Fn(&Mutation<'_>) + Send + Sync + 'static,
+{
+    #[inline]
+    fn on_mutation(&self, mutation: &Mutation<'_>) {
+        self(mutation)
+    }
+}
+
+/// Adds an observer for all `MetaInfo`s.
+pub fn add_global_observer<O: Observer>(observer: O) {
+    GLOBAL_OBSERVERS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Arc::new(observer));
+    HAS_GLOBAL_OBSERVERS.store(true, Ordering::Release);
+}
+
+impl MetaInfo {
+    /// Adds an observer for this `MetaInfo` and its derived children.
+    pub fn add_observer<O: Observer>(&mut self, observer: O) {
+        Arc::make_mut(self.observers.get_or_insert_with(Default::default)).push(Arc::new(observer));
+    }
+
+    /// Sets the tag passed to the observers with the mutations of this scope.
+    #[inline]
+    pub fn set_mutation_tag<T: Into<Cow<'static, str>>>(&mut self, tag: T) {
+        self.mutation_tag = Some(tag.into());
+    }
+
+    #[inline]
+    pub fn mutation_tag(&self) -> Option<&str> {
+        self.mutation_tag.as_deref()
+    }
+
+    #[inline]
+    pub(crate) fn is_observed(&self) -> bool {
+        self.observers.is_some() || HAS_GLOBAL_OBSERVERS.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn notify(
+        &self,
+        category: Category,
+        key: &str,
+        old: Option<Value<'_>>,
+        new: Option<Value<'_>>,
+    ) {
+        if !self.is_observed() {
+            return;
+        }
+        let mutation = Mutation {
+            category,
+            key,
+            old,
+            new,
+            tag: self.mutation_tag(),
+            sensitive: self.is_sensitive(key),
+        };
+        for observer in self.observers.iter().flat_map(|o| o.iter()) {
+            observer.on_mutation(&mutation);
+        }
+        if HAS_GLOBAL_OBSERVERS.load(Ordering::Acquire) {
+            for observer in GLOBAL_OBSERVERS
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .iter()
+            {
+                observer.on_mutation(&mutation);
+            }
+        }
+    }
+
+    /// Returns the text or bytes value of the key in the current scope.
+    pub(crate) fn current_value(&self, category: Category, key: &str) -> Option<Value<'_>> {
+        if category == Category::String {
+            return self
+                .smap
+                .as_ref()
+                .and_then(|smap| smap.get(key))
+                .map(|v| Value::Text(v));
+        }
+        #[cfg(feature = "bytes")]
+        return self.get_value_by_category(category, key);
+        #[cfg(not(feature = "bytes"))]
+        self.get_by_category(category, key).map(Value::Text)
+    }
+
+    /// Reports the k-v of `other` which replace those of this `MetaInfo` in
+    /// `extend`, before they do.
+    pub(crate) fn notify_extend(&self, other: &MetaInfo) {
+        for category in Category::ALL {
+            for (key, new) in other.entries(category) {
+                let old = self.current_value(category, key);
+                self.notify(category, key, old, Some(Value::Text(new)));
+            }
+            #[cfg(feature = "bytes")]
+            for (key, new) in other.bytes_entries(category) {
+                let old = self.current_value(category, key);
+                self.notify(category, key, old, Some(Value::Bytes(new)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BackwardWrite, ForwardWrite};
+    use std::sync::Mutex;
+
+    type Entry = (
+        Category,
+        String,
+        Option<String>,
+        Option<String>,
+        Option<String>,
+    );
+    type Log = Arc<Mutex<Vec<Entry>>>;
+
+    fn record(log: &Log) -> impl Observer {
+        let log = log.clone();
+        move |m: &Mutation<'_>| {
+            log.lock().unwrap().push((
+                m.category(),
+                m.key().to_owned(),
+                m.old_value().map(ToOwned::to_owned),
+                m.new_value().map(ToOwned::to_owned),
+                m.tag().map(ToOwned::to_owned),
+            ))
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let log = Log::default();
+        let mut mi = MetaInfo::new();
+        mi.add_observer(record(&log));
+        mi.set_persistent("k", "v1");
+        mi.set_persistent("k", "v2");
+        mi.del_persistent("k");
+        mi.del_persistent("k");
+        mi.set_backward_transient("b", "v");
+        mi.insert_string("s".into(), "v".into());
+        mi.remove_string("s");
+
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), 6);
+        assert_eq!(
+            log[1],
+            (
+                Category::Persistent,
+                "k".to_owned(),
+                Some("v1".to_owned()),
+                Some("v2".to_owned()),
+                None
+            )
+        );
+        assert_eq!(log[2].3, None);
+        assert_eq!(log[3].0, Category::BackwardTransient);
+        assert_eq!(log[5].0, Category::String);
+        assert_eq!(log[5].2.as_deref(), Some("v"));
+    }
+
+    #[test]
+    fn test_observer_debug_redacted() {
+        let debug = Arc::new(Mutex::new(Vec::new()));
+        let mut mi = MetaInfo::new();
+        mi.mark_sensitive(crate::policy::Pattern::exact("token"));
+        let sink = debug.clone();
+        mi.add_observer(move |m: &Mutation<'_>| sink.lock().unwrap().push(format!("{:?}", m)));
+        mi.set_persistent("token", "s3cr3t");
+        mi.set_persistent("tenant", "t1");
+
+        let debug = debug.lock().unwrap();
+        assert!(!debug[0].contains("s3cr3t"));
+        assert!(debug[0].contains(REDACTED));
+        assert!(debug[1].contains("t1"));
+    }
+
+    #[test]
+    fn test_observer_inherited() {
+        let log = Log::default();
+        let mut mi = MetaInfo::new();
+        mi.add_observer(record(&log));
+        mi.set_mutation_tag("parent");
+
+        let (_, mut child) = mi.derive();
+        assert_eq!(child.mutation_tag(), None);
+        child.set_mutation_tag("child");
+        child.set_transient("t", "v");
+
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].4.as_deref(), Some("child"));
+    }
+
+    #[test]
+    fn test_observer_clear_extend() {
+        let log = Log::default();
+        let mut mi = MetaInfo::new();
+        mi.add_observer(record(&log));
+        mi.set_persistent("k", "v1");
+        mi.insert_string("s".into(), "v".into());
+        mi.clear();
+
+        let mut other = MetaInfo::new();
+        other.set_persistent("k", "v2");
+        other.set_backward_downstream("d", "v");
+        mi.extend(other);
+
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), 5);
+        assert_eq!(log[2].0, Category::String);
+        assert_eq!(log[2].3, None);
+        assert_eq!(log[3].2.as_deref(), Some("v1"));
+        assert_eq!(log[3].3.as_deref(), Some("v2"));
+        assert_eq!(log[4].0, Category::BackwardDownstream);
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_observer_bytes() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut mi = MetaInfo::new();
+        let sink = log.clone();
+        mi.add_observer(move |m: &Mutation<'_>| {
+            sink.lock().unwrap().push((
+                m.key().to_owned(),
+                m.old_value().map(ToOwned::to_owned),
+                m.old_bytes().cloned(),
+                m.new_bytes().cloned(),
+            ))
+        });
+        mi.set_transient("k", "text");
+        mi.set_transient_bytes("k", vec![1]);
+        mi.set_transient_bytes("k", vec![2]);
+        mi.set_transient("k", "text");
+        mi.set_backward_transient_bytes("b", vec![3]);
+        mi.del_backward_transient_bytes("b");
+        mi.del_backward_transient_bytes("b");
+
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), 6);
+        assert_eq!(
+            log[1],
+            (
+                "k".to_owned(),
+                Some("text".to_owned()),
+                None,
+                Some(vec![1].into())
+            )
+        );
+        assert_eq!(log[2].2, Some(vec![1].into()));
+        assert_eq!(log[3].2, Some(vec![2].into()));
+        assert_eq!(log[3].3, None);
+        assert_eq!(log[5].2, Some(vec![3].into()));
+    }
+
+    #[cfg(feature = "multi_value")]
+    #[test]
+    fn test_observer_append() {
+        let log = Log::default();
+        let mut mi = MetaInfo::new();
+        mi.add_observer(record(&log));
+        mi.append_upstream("hops", "a");
+        mi.append_upstream("hops", "b");
+        mi.set_split_values(Category::Upstream, "hops", "c,d", &Default::default());
+
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), 5);
+        assert_eq!(log[1].2, None);
+        assert_eq!(log[1].3.as_deref(), Some("b"));
+        assert_eq!(log[2].2.as_deref(), Some("a"));
+        assert_eq!(log[2].3, None);
+        assert_eq!(log[4].3.as_deref(), Some("d"));
+    }
+
+    #[test]
+    fn test_global_observer() {
+        let log = Log::default();
+        let sink = record(&log);
+        add_global_observer(move |m: &Mutation<'_>| {
+            if m.key() == "test_global_observer" {
+                sink.on_mutation(m)
+            }
+        });
+
+        let mut mi = MetaInfo::new();
+        mi.set_upstream("test_global_observer", "v");
+        assert_eq!(log.lock().unwrap().len(), 1);
+        assert_eq!(log.lock().unwrap()[0].0, Category::Upstream);
+    }
+}
@@ -0,0 +1,345 @@
+//! Bridge between `MetaInfo` and OpenTelemetry.
+//!
+//! - persistents can be converted from and to [`Baggage`];
+//! - an OpenTelemetry [`Context`] can be embedded into `MetaInfo` as a typed entry,
+//!   see [`set_context`] and [`context`];
+//! - [`MetaInfoPropagator`] is a [`TextMapPropagator`] using the crate's HTTP
+//!   prefixes, so it can run side by side with the OpenTelemetry propagators.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::opentelemetry::{persistents_to_baggage, set_persistents_from_baggage};
+//! use metainfo::{ForwardRead, ForwardWrite, MetaInfo};
+//!
+//! let mut mi = MetaInfo::new();
+//! mi.set_persistent("tenant", "t1");
+//! let baggage = persistents_to_baggage(&mi);
+//!
+//! let mut other = MetaInfo::new();
+//! set_persistents_from_baggage(&mut other, &baggage);
+//! assert_eq!(other.get_persistent("tenant"), Some("t1"));
+//! ```
+
+use crate::policy::{InboundPolicy, OutboundPolicy};
+use crate::{
+    Budget, Category, ForwardRead, ForwardWrite, MetaInfo, HTTP_PREFIX_BUDGET_PERSISTENT,
+    HTTP_PREFIX_BUDGET_TRANSIENT, HTTP_PREFIX_PERSISTENT, HTTP_PREFIX_TRANSIENT,
+};
+use ::opentelemetry::baggage::Baggage;
+use ::opentelemetry::propagation::TextMapPropagator;
+use ::opentelemetry::propagation::{text_map_propagator::FieldIter, Extractor, Injector};
+use ::opentelemetry::Context;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Converts the persistents into `Baggage`.
+///
+/// Entries exceeding the W3C baggage limits are dropped, as are those the
+/// outbound policy of `MetaInfo` doesn't allow.
+pub fn persistents_to_baggage(mi: &MetaInfo) -> Baggage {
+    let mut baggage = Baggage::new();
+    for (k, v) in mi.persistents() {
+        if !mi.outbound_allows(Category::Persistent, k) {
+            continue;
+        }
+        baggage.insert(k.to_owned(), v.to_owned());
+    }
+    baggage
+}
+
+/// Sets the entries of `Baggage` as persistents, the baggage metadata is ignored.
+pub fn set_persistents_from_baggage(mi: &mut MetaInfo, baggage: &Baggage) {
+    for (k, (v, _)) in baggage {
+        mi.set_persistent(k.as_str().to_owned(), v.as_str().to_owned());
+    }
+}
+
+/// Embeds the OpenTelemetry `Context` into `MetaInfo`.
+#[inline]
+pub fn set_context(mi: &mut MetaInfo, cx: Context) {
+    mi.insert(cx);
+}
+
+/// Returns the OpenTelemetry `Context` embedded into `MetaInfo`, if any.
+#[inline]
+pub fn context(mi: &MetaInfo) -> Option<&Context> {
+    mi.get::<Context>()
+}
+
+/// Attaches `MetaInfo` to the OpenTelemetry `Context`, which is injected by
+/// [`MetaInfoPropagator`].
+#[inline]
+pub fn with_metainfo(cx: &Context, mi: MetaInfo) -> Context {
+    cx.with_value(Arc::new(mi))
+}
+
+/// Returns the `MetaInfo` attached to the OpenTelemetry `Context`, if any.
+///
+/// The extracted `MetaInfo` is read-only, use `MetaInfo::from` to continue with it.
+#[inline]
+pub fn metainfo(cx: &Context) -> Option<&Arc<MetaInfo>> {
+    cx.get::<Arc<MetaInfo>>()
+}
+
+/// A `TextMapPropagator` which carries persistents and transients with the
+/// `rpc-persist-` and `rpc-transit-` prefixes.
+///
+/// It injects the `MetaInfo` attached to the context, or the current [`METAINFO`]
+/// if there is none. It extracts into a new `MetaInfo` attached to the context.
+///
+/// [`METAINFO`]: crate::METAINFO
+#[derive(Debug, Clone, Default)]
+pub struct MetaInfoPropagator {
+    inbound_policy: Option<Arc<InboundPolicy>>,
+    outbound_policy: Option<Arc<OutboundPolicy>>,
+}
+
+impl MetaInfoPropagator {
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Filters the extracted metainfo by the policy.
+    pub fn with_inbound_policy(mut self, policy: Arc<InboundPolicy>) -> Self {
+        self.inbound_policy = Some(policy);
+        self
+    }
+
+    /// Filters the injected metainfo by the policy, on top of the outbound
+    /// policy of the `MetaInfo`.
+    pub fn with_outbound_policy(mut self, policy: Arc<OutboundPolicy>) -> Self {
+        self.outbound_policy = Some(policy);
+        self
+    }
+}
+
+fn inject(mi: &MetaInfo, policy: Option<&OutboundPolicy>, injector: &mut dyn Injector) {
+    let allows = |category, key| {
+        policy
+            .map(|policy| policy.allows(category, key))
+            .unwrap_or(true)
+    };
+    let now = SystemTime::now();
+    for (prefix, budget_prefix, category, map) in [
+        (
+            HTTP_PREFIX_PERSISTENT,
+            HTTP_PREFIX_BUDGET_PERSISTENT,
+            Category::Persistent,
+            mi.persistents(),
+        ),
+        (
+            HTTP_PREFIX_TRANSIENT,
+            HTTP_PREFIX_BUDGET_TRANSIENT,
+            Category::Transient,
+            mi.transients(),
+        ),
+    ] {
+        for (k, v) in map.filter(|(k, _)| allows(category, k)) {
+            match mi.outbound_budget(category, k, now) {
+                Some(Some(budget)) => {
+                    injector.set(&format!("{}{}", budget_prefix, k), budget.to_header_value())
+                }
+                Some(None) => {}
+                None => continue,
+            }
+            injector.set(&format!("{}{}", prefix, k), v.to_string());
+        }
+    }
+    #[cfg(feature = "bytes")]
+    for (prefix, category) in [
+        (HTTP_PREFIX_PERSISTENT, Category::Persistent),
+        (HTTP_PREFIX_TRANSIENT, Category::Transient),
+    ] {
+        for (k, v) in mi.outbound_bytes_entries(category) {
+            if !allows(category, k) {
+                continue;
+            }
+            use base64::Engine;
+            injector.set(
+                &format!("{}{}-bin", prefix, k),
+                crate::codec::BASE64.encode(v),
+            );
+        }
+    }
+}
+
+impl TextMapPropagator for MetaInfoPropagator {
+    fn inject_context(&self, cx: &Context, injector: &mut dyn Injector) {
+        match metainfo(cx) {
+            Some(mi) => inject(mi, self.outbound_policy.as_deref(), injector),
+            #[cfg(feature = "task_local")]
+            None => {
+                let _ = crate::METAINFO.try_with(|mi| {
+                    if let Ok(mi) = mi.try_borrow() {
+                        inject(&mi, self.outbound_policy.as_deref(), injector)
+                    }
+                });
+            }
+            #[cfg(not(feature = "task_local"))]
+            None => {}
+        }
+    }
+
+    fn extract_with_context(&self, cx: &Context, extractor: &dyn Extractor) -> Context {
+        let mut mi = MetaInfo::new();
+        if let Some(policy) = self.inbound_policy.as_ref() {
+            mi.set_inbound_policy(policy.clone());
+        }
+        let mut budgets = Vec::new();
+        for key in extractor.keys() {
+            let value = match extractor.get(key) {
+                Some(value) => value.to_owned(),
+                None => continue,
+            };
+            let key = key.to_ascii_lowercase();
+            #[cfg(feature = "bytes")]
+            if let Some(key) = key.strip_suffix("-bin") {
+                use base64::Engine;
+                if let Ok(value) = crate::codec::BASE64.decode(value) {
+                    if let Some(key) = key.strip_prefix(HTTP_PREFIX_PERSISTENT) {
+                        if mi.inbound_allows(Category::Persistent, key) {
+                            let key = mi.inbound_key(Category::Persistent, key);
+                            mi.set_persistent_bytes(key, value);
+                        }
+                    } else if let Some(key) = key.strip_prefix(HTTP_PREFIX_TRANSIENT) {
+                        if mi.inbound_allows(Category::Upstream, key) {
+                            let key = mi.inbound_key(Category::Upstream, key);
+                            mi.set_upstream_bytes(key, value);
+                        }
+                    }
+                }
+                continue;
+            }
+            if let Some(k) = key.strip_prefix(HTTP_PREFIX_PERSISTENT) {
+                mi.set_inbound(Category::Persistent, k, value.into());
+            } else if let Some(k) = key.strip_prefix(HTTP_PREFIX_TRANSIENT) {
+                mi.set_inbound(Category::Upstream, k, value.into());
+            } else if let Some(k) = key.strip_prefix(HTTP_PREFIX_BUDGET_PERSISTENT) {
+                budgets.push((Category::Persistent, k.to_owned(), value));
+            } else if let Some(k) = key.strip_prefix(HTTP_PREFIX_BUDGET_TRANSIENT) {
+                budgets.push((Category::Upstream, k.to_owned(), value));
+            }
+        }
+        // the budgets apply to the values, which may come later
+        let now = SystemTime::now();
+        for (category, key, value) in budgets {
+            if let Some(budget) = Budget::parse_header_value(&value) {
+                mi.set_inbound_budget(category, &key, budget, now);
+            }
+        }
+        with_metainfo(cx, mi)
+    }
+
+    fn fields(&self) -> FieldIter<'_> {
+        // the keys are not known in advance
+        FieldIter::new(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::Pattern;
+    use ::opentelemetry::baggage::BaggageExt;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_baggage() {
+        let cx = Context::new().with_baggage([::opentelemetry::KeyValue::new("k", "v")]);
+        let mut mi = MetaInfo::new();
+        set_persistents_from_baggage(&mut mi, cx.baggage());
+        assert_eq!(mi.get_persistent("k"), Some("v"));
+
+        let baggage = persistents_to_baggage(&mi);
+        assert_eq!(baggage.get("k").map(|v| v.as_str()), Some("v"));
+        assert_eq!(baggage.len(), 1);
+    }
+
+    #[test]
+    fn test_context() {
+        #[derive(Debug, PartialEq)]
+        struct Marker(u8);
+
+        let mut mi = MetaInfo::new();
+        assert!(context(&mi).is_none());
+        set_context(&mut mi, Context::new().with_value(Marker(1)));
+        let (_, child) = mi.derive();
+        assert_eq!(context(&child).unwrap().get::<Marker>(), Some(&Marker(1)));
+    }
+
+    #[test]
+    fn test_propagator() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("tenant", "t1");
+        mi.set_transient("caller", "svc");
+        mi.set_upstream("stale", "x");
+
+        let propagator = MetaInfoPropagator::new();
+        let mut headers = HashMap::new();
+        propagator.inject_context(&with_metainfo(&Context::new(), mi), &mut headers);
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers["rpc-persist-tenant"], "t1");
+        assert_eq!(headers["rpc-transit-caller"], "svc");
+
+        headers.insert("traceparent".to_owned(), "ignored".to_owned());
+        let cx = propagator.extract(&headers);
+        let mi = metainfo(&cx).unwrap();
+        assert_eq!(mi.get_persistent("tenant"), Some("t1"));
+        assert_eq!(mi.get_upstream("caller"), Some("svc"));
+        assert_eq!(mi.get_transient("caller"), None);
+    }
+
+    #[test]
+    fn test_propagator_outbound_policy() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("tenant", "t1");
+        mi.set_persistent("user", "u1");
+        mi.set_transient("caller", "svc");
+        mi.set_outbound_policy(Arc::new(
+            OutboundPolicy::new().deny(Category::Transient, Pattern::exact("caller")),
+        ));
+
+        let propagator = MetaInfoPropagator::new().with_outbound_policy(Arc::new(
+            OutboundPolicy::new().deny(Category::Persistent, Pattern::exact("user")),
+        ));
+        let mut headers = HashMap::new();
+        propagator.inject_context(&with_metainfo(&Context::new(), mi), &mut headers);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers["rpc-persist-tenant"], "t1");
+    }
+
+    #[test]
+    fn test_propagator_budget() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent_with_budget("debug", "1", Budget::hops(1));
+
+        let propagator = MetaInfoPropagator::new();
+        let mut headers = HashMap::new();
+        propagator.inject_context(&with_metainfo(&Context::new(), mi), &mut headers);
+        assert_eq!(headers["rpc-budget-persist-debug"], "hops=0");
+
+        let cx = propagator.extract(&headers);
+        let mi = MetaInfo::from(metainfo(&cx).unwrap().clone());
+        assert_eq!(mi.get_persistent("debug"), Some("1"));
+        let mut headers = HashMap::new();
+        propagator.inject_context(&with_metainfo(&Context::new(), mi), &mut headers);
+        assert!(headers.is_empty());
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn test_propagator_bytes() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent_bytes("auth", vec![0xde, 0xad]);
+
+        let propagator = MetaInfoPropagator::new();
+        let mut headers = HashMap::new();
+        propagator.inject_context(&with_metainfo(&Context::new(), mi), &mut headers);
+        assert_eq!(headers["rpc-persist-auth-bin"], "3q0");
+
+        let cx = propagator.extract(&headers);
+        let mi = metainfo(&cx).unwrap();
+        assert_eq!(mi.get_persistent_bytes("auth").unwrap()[..], [0xde, 0xad]);
+    }
+}
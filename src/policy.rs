@@ -0,0 +1,303 @@
+//! Allow and deny lists for metainfo crossing a trust boundary.
+//!
+//! An [`InboundPolicy`] decides which keys may enter the current service, an
+//! [`OutboundPolicy`] decides which keys may leave it. Both match the key
+//! without the transport prefix, e.g. `tenant` rather than `rpc-persist-tenant`.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::policy::{InboundPolicy, Pattern};
+//! use metainfo::{Category, ForwardRead, ForwardWrite, MetaInfo};
+//! use std::sync::Arc;
+//!
+//! let policy = InboundPolicy::new()
+//!     .deny(Category::Persistent, Pattern::prefix("internal-"))
+//!     .allow(Category::Upstream, Pattern::glob("x-*"));
+//! assert!(!policy.allows(Category::Persistent, "internal-user"));
+//! assert!(policy.allows(Category::Upstream, "x-request-id"));
+//!
+//! let mut mi = MetaInfo::new();
+//! mi.set_inbound_policy(Arc::new(policy));
+//! mi.strip_http_prefix_and_set_persistent("rpc-persist-internal-user", "admin");
+//! mi.strip_http_prefix_and_set_persistent("rpc-persist-tenant", "t1");
+//! assert_eq!(mi.get_persistent("internal-user"), None);
+//! assert_eq!(mi.get_persistent("tenant"), Some("t1"));
+//! ```
+
+use crate::{Category, MetaInfo};
+use fxhash::FxHashMap;
+use std::borrow::Cow;
+
+/// Matches a metainfo key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pattern {
+    /// Matches the key exactly.
+    Exact(Cow<'static, str>),
+    /// Matches keys starting with the given prefix.
+    Prefix(Cow<'static, str>),
+    /// Matches keys against a glob, `*` matches any sequence and `?` matches
+    /// a single character.
+    Glob(Cow<'static, str>),
+}
+
+impl Pattern {
+    pub fn exact<S: Into<Cow<'static, str>>>(s: S) -> Self {
+        Pattern::Exact(s.into())
+    }
+
+    pub fn prefix<S: Into<Cow<'static, str>>>(s: S) -> Self {
+        Pattern::Prefix(s.into())
+    }
+
+    pub fn glob<S: Into<Cow<'static, str>>>(s: S) -> Self {
+        Pattern::Glob(s.into())
+    }
+
+    /// Returns whether the key matches this pattern.
+    pub fn matches(&self, key: &str) -> bool {
+        match self {
+            Pattern::Exact(s) => s == key,
+            Pattern::Prefix(s) => key.starts_with(s.as_ref()),
+            Pattern::Glob(s) if s.is_ascii() && key.is_ascii() => {
+                glob_match(s.as_bytes(), key.as_bytes(), b'*', b'?')
+            }
+            // `?` matches a char, which may take several bytes
+            Pattern::Glob(s) => {
+                let pattern: Vec<char> = s.chars().collect();
+                let key: Vec<char> = key.chars().collect();
+                glob_match(&pattern, &key, '*', '?')
+            }
+        }
+    }
+}
+
+fn glob_match<T: PartialEq>(pattern: &[T], key: &[T], star: T, any: T) -> bool {
+    let (mut p, mut k) = (0, 0);
+    // position of the last `*` in pattern and the key position it matched up to
+    let mut backtrack: Option<(usize, usize)> = None;
+    while k < key.len() {
+        match pattern.get(p) {
+            Some(c) if *c == star => {
+                backtrack = Some((p, k));
+                p += 1;
+            }
+            Some(c) if *c == any || *c == key[k] => {
+                p += 1;
+                k += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    k = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == star)
+}
+
+/// Allow and deny lists of one category.
+///
+/// A key passes if the allow list is empty or any allow pattern matches it,
+/// and no deny pattern matches it.
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    allow: Vec<Pattern>,
+    deny: Vec<Pattern>,
+}
+
+impl Rules {
+    pub fn allows(&self, key: &str) -> bool {
+        (self.allow.is_empty() || self.allow.iter().any(|p| p.matches(key)))
+            && !self.deny.iter().any(|p| p.matches(key))
+    }
+
+    /// Returns whether an allow pattern matches the key, and no deny pattern.
+    fn explicitly_allows(&self, key: &str) -> bool {
+        self.allow.iter().any(|p| p.matches(key)) && !self.deny.iter().any(|p| p.matches(key))
+    }
+}
+
+macro_rules! policy_impl {
+    ($name:ident) => {
+        impl $name {
+            /// Creates a policy which allows everything.
+            #[inline]
+            pub fn new() -> Self {
+                Default::default()
+            }
+
+            /// Adds an allow pattern for the category.
+            pub fn allow(mut self, category: Category, pattern: Pattern) -> Self {
+                self.rules.entry(category).or_default().allow.push(pattern);
+                self
+            }
+
+            /// Adds a deny pattern for the category.
+            pub fn deny(mut self, category: Category, pattern: Pattern) -> Self {
+                self.rules.entry(category).or_default().deny.push(pattern);
+                self
+            }
+
+            /// Returns the rules of the category, if any is configured.
+            #[inline]
+            pub fn rules(&self, category: Category) -> Option<&Rules> {
+                self.rules.get(&category)
+            }
+
+            /// Returns whether the key of the category passes this policy.
+            #[inline]
+            pub fn allows(&self, category: Category, key: &str) -> bool {
+                self.rules
+                    .get(&category)
+                    .map(|rules| rules.allows(key))
+                    .unwrap_or(true)
+            }
+
+            /// Deletes all entries in the current scope of `MetaInfo` which don't
+            /// pass this policy.
+            pub fn apply(&self, mi: &mut MetaInfo) {
+                for (category, rules) in self.rules.iter() {
+                    for key in mi.keys(*category) {
+                        if !rules.allows(&key) {
+                            mi.del_by_category(*category, &key);
+                        }
+                    }
+                }
+            }
+        }
+    };
+}
+
+/// Filters the metainfo received from outside of the trust domain.
+///
+/// Once attached by [`MetaInfo::set_inbound_policy`], the `strip_*_prefix_and_set_*`
+/// methods drop the keys that don't pass it. Use [`InboundPolicy::apply`] for
+/// metainfo that is extracted in other ways.
+#[derive(Debug, Clone, Default)]
+pub struct InboundPolicy {
+    rules: FxHashMap<Category, Rules>,
+}
+
+policy_impl!(InboundPolicy);
+
+impl InboundPolicy {
+    /// Returns whether the key of the category is allowed by an allow pattern,
+    /// rather than by the absence of one.
+    pub(crate) fn explicitly_allows(&self, category: Category, key: &str) -> bool {
+        self.rules
+            .get(&category)
+            .map(|rules| rules.explicitly_allows(key))
+            .unwrap_or(false)
+    }
+}
+
+/// Filters the metainfo sent to outside of the trust domain.
+///
+/// Once attached by [`MetaInfo::set_outbound_policy`], the codecs leave out the
+/// keys that don't pass it. [`OutboundPolicy::apply`] deletes them instead,
+/// e.g. from a derived `MetaInfo` handed to other code.
+#[derive(Debug, Clone, Default)]
+pub struct OutboundPolicy {
+    rules: FxHashMap<Category, Rules>,
+}
+
+policy_impl!(OutboundPolicy);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BackwardRead, BackwardWrite, ForwardRead, ForwardWrite};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_pattern() {
+        assert!(Pattern::exact("a").matches("a"));
+        assert!(!Pattern::exact("a").matches("ab"));
+        assert!(Pattern::prefix("a").matches("ab"));
+        assert!(!Pattern::prefix("b").matches("ab"));
+
+        assert!(Pattern::glob("*").matches(""));
+        assert!(Pattern::glob("a*c").matches("abbbc"));
+        assert!(Pattern::glob("a*c").matches("ac"));
+        assert!(!Pattern::glob("a*c").matches("acb"));
+        assert!(Pattern::glob("a?c").matches("abc"));
+        assert!(!Pattern::glob("a?c").matches("ac"));
+        assert!(Pattern::glob("*-id-*").matches("x-id-id-y"));
+        assert!(Pattern::glob("a?c").matches("aéc"));
+        assert!(Pattern::glob("é?").matches("éé"));
+        assert!(!Pattern::glob("a??c").matches("aéc"));
+    }
+
+    #[test]
+    fn test_rules() {
+        let policy = InboundPolicy::new()
+            .allow(Category::Persistent, Pattern::prefix("tenant"))
+            .deny(Category::Persistent, Pattern::exact("tenant-admin"));
+        assert!(policy.allows(Category::Persistent, "tenant-id"));
+        assert!(!policy.allows(Category::Persistent, "tenant-admin"));
+        assert!(!policy.allows(Category::Persistent, "user"));
+        assert!(policy.allows(Category::Upstream, "user"));
+    }
+
+    #[test]
+    fn test_inbound() {
+        let policy = InboundPolicy::new()
+            .deny(Category::Persistent, Pattern::glob("*"))
+            .deny(Category::BackwardDownstream, Pattern::exact("secret"));
+        let mut mi = MetaInfo::new();
+        mi.set_inbound_policy(std::sync::Arc::new(policy));
+
+        mi.strip_rpc_prefix_and_set_persistent("RPC_PERSIST_a", "a");
+        mi.strip_rpc_prefix_and_set_upstream("RPC_TRANSIT_b", "b");
+        mi.strip_http_prefix_and_set_backward_downstream("rpc-backward-secret", "c");
+        mi.strip_http_prefix_and_set_backward_downstream("rpc-backward-d", "d");
+        assert_eq!(mi.get_persistent("a"), None);
+        assert_eq!(mi.get_upstream("b"), Some("b"));
+        assert_eq!(mi.get_backward_downstream("secret"), None);
+        assert_eq!(mi.get_backward_downstream("d"), Some("d"));
+
+        // the policy is inherited by children
+        let (_, mut child) = mi.derive();
+        child.strip_rpc_prefix_and_set_persistent("RPC_PERSIST_a", "a");
+        assert_eq!(child.get_persistent("a"), None);
+    }
+
+    #[test]
+    fn test_outbound() {
+        let policy = OutboundPolicy::new()
+            .allow(Category::Persistent, Pattern::exact("tenant"))
+            .deny(Category::BackwardTransient, Pattern::prefix("debug-"));
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("tenant", "t1");
+        mi.set_persistent("user", "u1");
+        mi.set_transient("user", "u1");
+        mi.set_backward_transient("debug-trace", "1");
+
+        let (mi, mut outgoing) = mi.derive();
+        policy.apply(&mut outgoing);
+        assert_eq!(outgoing.get_persistent("tenant"), Some("t1"));
+        assert_eq!(outgoing.get_persistent("user"), None);
+        assert_eq!(outgoing.get_transient("user"), Some("u1"));
+        assert_eq!(outgoing.get_backward_transient("debug-trace"), None);
+
+        assert_eq!(mi.get_persistent("user"), Some("u1"));
+
+        // attached, the codecs leave the keys out and the values untouched
+        let (_, mut mi) = mi.derive();
+        mi.set_outbound_policy(Arc::new(policy));
+        let (mut request, mut response) = (Vec::new(), Vec::new());
+        crate::codec::theader::encode_request(&mi, &mut request).unwrap();
+        crate::codec::theader::encode_response(&mi, &mut response).unwrap();
+        let mut received = MetaInfo::new();
+        crate::codec::theader::decode_request(&request, &mut received).unwrap();
+        crate::codec::theader::decode_response(&response, &mut received).unwrap();
+        assert_eq!(received.get_persistent("tenant"), Some("t1"));
+        assert_eq!(received.get_persistent("user"), None);
+        assert_eq!(received.get_upstream("user"), Some("u1"));
+        assert_eq!(received.get_backward_downstream("debug-trace"), None);
+        assert_eq!(mi.get_persistent("user"), Some("u1"));
+    }
+}
@@ -0,0 +1,294 @@
+//! Propagates `MetaInfo` into rayon parallel iterators.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::rayon::ParallelIteratorExt;
+//! use metainfo::{BackwardRead, BackwardWrite, ForwardRead, ForwardWrite, MetaInfo};
+//! use rayon::prelude::*;
+//!
+//! let mut mi = MetaInfo::new();
+//! mi.set_persistent("tenant", "t1");
+//!
+//! (0..100).into_par_iter().with_metainfo(&mut mi).for_each(|i| {
+//!     metainfo::current::with_current_mut(|mi| {
+//!         let mi = mi.unwrap();
+//!         assert_eq!(mi.get_persistent("tenant"), Some("t1"));
+//!         if i == 42 {
+//!             mi.set_backward_transient("found", i.to_string());
+//!         }
+//!     });
+//! });
+//! assert_eq!(mi.get_backward_transient("found"), Some("42"));
+//! ```
+
+use crate::current::set_current;
+use crate::kv::Node;
+use crate::MetaInfo;
+use ::rayon::iter::plumbing::{Consumer, Folder, UnindexedConsumer};
+use ::rayon::iter::ParallelIterator;
+use std::sync::{Arc, Mutex};
+
+/// Extends `ParallelIterator` with [`with_metainfo`](ParallelIteratorExt::with_metainfo).
+pub trait ParallelIteratorExt: ParallelIterator {
+    /// Runs the rest of the iterator chain with a child derived from `mi` set as
+    /// the current `MetaInfo` of the worker thread, see [`crate::current`].
+    ///
+    /// Only the adapters after this one can see the `MetaInfo`, so call it right
+    /// after creating the parallel iterator. The children start without the
+    /// backward metainfo of `mi`, and when the iterator is done, the backward
+    /// metainfo set by the workers is merged back into `mi`. `mi` is restored
+    /// even if the iterator panics.
+    fn with_metainfo(self, mi: &mut MetaInfo) -> WithMetaInfo<'_, Self> {
+        WithMetaInfo { base: self, mi }
+    }
+}
+
+impl<I: ParallelIterator> ParallelIteratorExt for I {}
+
+/// Parallel iterator returned by [`ParallelIteratorExt::with_metainfo`].
+pub struct WithMetaInfo<'a, I> {
+    base: I,
+    mi: &'a mut MetaInfo,
+}
+
+impl<I: ParallelIterator> ParallelIterator for WithMetaInfo<'_, I> {
+    type Item = I::Item;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        let mut parent = std::mem::take(self.mi);
+        // only the backward metainfo set by the workers is merged back
+        let backward_node = parent.backward_node.take();
+        let restore = Restore {
+            mi: self.mi,
+            parent: Some(Arc::new(parent)),
+            backward_node,
+            backward: Mutex::new(Vec::new()),
+        };
+        self.base.drive_unindexed(MetaInfoConsumer {
+            base: consumer,
+            parent: restore.parent.as_ref().unwrap(),
+            backward: &restore.backward,
+        })
+    }
+}
+
+/// Puts the `MetaInfo` back to the caller when the iterator is done or panics.
+struct Restore<'a> {
+    mi: &'a mut MetaInfo,
+    parent: Option<Arc<MetaInfo>>,
+    backward_node: Option<Node>,
+    backward: Mutex<Vec<Node>>,
+}
+
+impl Drop for Restore<'_> {
+    fn drop(&mut self) {
+        // all the children are dropped by now
+        if let Some(parent) = self.parent.take() {
+            *self.mi = Arc::try_unwrap(parent).unwrap_or_else(MetaInfo::from);
+        }
+        self.mi.backward_node = self.backward_node.take();
+        let backward = self.backward.get_mut().unwrap_or_else(|e| e.into_inner());
+        for node in backward.drain(..) {
+            // through `extend`, so the observers see the merged values
+            self.mi.extend(MetaInfo {
+                backward_node: Some(node),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+struct MetaInfoConsumer<'a, C> {
+    base: C,
+    parent: &'a Arc<MetaInfo>,
+    backward: &'a Mutex<Vec<Node>>,
+}
+
+impl<'a, C> MetaInfoConsumer<'a, C> {
+    fn with_base<B>(&self, base: B) -> MetaInfoConsumer<'a, B> {
+        MetaInfoConsumer {
+            base,
+            parent: self.parent,
+            backward: self.backward,
+        }
+    }
+}
+
+impl<'a, T, C: Consumer<T>> Consumer<T> for MetaInfoConsumer<'a, C> {
+    type Folder = MetaInfoFolder<'a, C::Folder>;
+    type Reducer = C::Reducer;
+    type Result = C::Result;
+
+    fn split_at(self, index: usize) -> (Self, Self, Self::Reducer) {
+        let (parent, backward) = (self.parent, self.backward);
+        let (left, right, reducer) = self.base.split_at(index);
+        let consumer = |base| MetaInfoConsumer {
+            base,
+            parent,
+            backward,
+        };
+        (consumer(left), consumer(right), reducer)
+    }
+
+    fn into_folder(self) -> Self::Folder {
+        MetaInfoFolder {
+            base: self.base.into_folder(),
+            parent: self.parent,
+            mi: None,
+            backward: self.backward,
+        }
+    }
+
+    #[inline]
+    fn full(&self) -> bool {
+        self.base.full()
+    }
+}
+
+impl<T, C: UnindexedConsumer<T>> UnindexedConsumer<T> for MetaInfoConsumer<'_, C> {
+    fn split_off_left(&self) -> Self {
+        self.with_base(self.base.split_off_left())
+    }
+
+    fn to_reducer(&self) -> Self::Reducer {
+        self.base.to_reducer()
+    }
+}
+
+struct MetaInfoFolder<'a, F> {
+    base: F,
+    parent: &'a Arc<MetaInfo>,
+    // derived on the first item
+    mi: Option<MetaInfo>,
+    backward: &'a Mutex<Vec<Node>>,
+}
+
+impl<T, F: Folder<T>> Folder<T> for MetaInfoFolder<'_, F> {
+    type Result = F::Result;
+
+    fn consume(self, item: T) -> Self {
+        let MetaInfoFolder {
+            base,
+            parent,
+            mi,
+            backward,
+        } = self;
+        let guard = set_current(mi.unwrap_or_else(|| MetaInfo::from(parent.clone())));
+        let base = base.consume(item);
+        MetaInfoFolder {
+            base,
+            parent,
+            mi: Some(guard.finish()),
+            backward,
+        }
+    }
+
+    fn complete(self) -> Self::Result {
+        if let Some(node) = self.mi.and_then(|mi| mi.backward_node) {
+            self.backward
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(node);
+        }
+        self.base.complete()
+    }
+
+    #[inline]
+    fn full(&self) -> bool {
+        self.base.full()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::current::{with_current, with_current_mut};
+    use crate::{BackwardRead, BackwardWrite, ForwardRead, ForwardWrite};
+    use ::rayon::prelude::*;
+
+    #[test]
+    fn test_with_metainfo() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("k", "v");
+        mi.set_backward_transient("origin", "caller");
+        mi.insert(1u8);
+
+        let values: Vec<_> = (0..64)
+            .into_par_iter()
+            .with_metainfo(&mut mi)
+            .map(|i| {
+                with_current_mut(|mi| {
+                    let mi = mi.unwrap();
+                    mi.set_backward_transient(format!("k{}", i), i.to_string());
+                    mi.get_persistent("k").map(String::from)
+                })
+            })
+            .collect();
+        assert_eq!(values.len(), 64);
+        assert!(values.iter().all(|v| v.as_deref() == Some("v")));
+
+        assert_eq!(mi.get::<u8>(), Some(&1));
+        assert_eq!(mi.get_backward_transient("origin"), Some("caller"));
+        assert_eq!(mi.backward_transients().len(), 65);
+        for i in 0..64 {
+            assert_eq!(
+                mi.get_backward_transient(format!("k{}", i)),
+                Some(i.to_string().as_str())
+            );
+        }
+
+        // nothing is left in the worker threads
+        (0..64).into_par_iter().for_each(|_| {
+            assert!(with_current(|mi| mi.is_none()));
+        });
+    }
+
+    #[test]
+    fn test_panic() {
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("k", "v");
+        mi.set_backward_transient("origin", "caller");
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            (0..64)
+                .into_par_iter()
+                .with_metainfo(&mut mi)
+                .for_each(|i| {
+                    with_current_mut(|mi| {
+                        mi.unwrap().set_backward_transient(format!("k{}", i), "v")
+                    });
+                    if i == 42 {
+                        panic!("worker panicked");
+                    }
+                })
+        }));
+        assert!(result.is_err());
+        assert_eq!(mi.get_persistent("k"), Some("v"));
+        assert_eq!(mi.get_backward_transient("origin"), Some("caller"));
+    }
+
+    #[test]
+    fn test_observer() {
+        let merged = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut mi = MetaInfo::new();
+        let counter = merged.clone();
+        mi.add_observer(move |m: &crate::observe::Mutation<'_>| {
+            // the merge is made on the restored MetaInfo, with its tag
+            if m.tag() == Some("parent") {
+                counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+        });
+        mi.set_mutation_tag("parent");
+
+        (0..64)
+            .into_par_iter()
+            .with_metainfo(&mut mi)
+            .for_each(|i| {
+                with_current_mut(|mi| mi.unwrap().set_backward_transient(format!("k{}", i), "v"));
+            });
+        assert_eq!(merged.load(std::sync::atomic::Ordering::Relaxed), 64);
+    }
+}
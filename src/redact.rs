@@ -0,0 +1,135 @@
+//! Redaction of sensitive values in debug output and logs.
+//!
+//! A key is sensitive if it matches a pattern marked on the `MetaInfo` by
+//! [`MetaInfo::mark_sensitive`] or globally by [`mark_sensitive`]. The values of
+//! sensitive keys are shown as [`REDACTED`] by the `Debug` of `MetaInfo` and by
+//! [`MetaInfo::redacted`], while the codecs still send them as is.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::policy::Pattern;
+//! use metainfo::{Category, ForwardRead, ForwardWrite, MetaInfo};
+//!
+//! let mut mi = MetaInfo::new();
+//! mi.mark_sensitive(Pattern::glob("*token*"));
+//! mi.set_transient("auth_token", "s3cr3t");
+//! mi.set_transient("tenant", "t1");
+//!
+//! let mut entries: Vec<_> = mi.redacted(Category::Transient).collect();
+//! entries.sort();
+//! assert_eq!(entries, [("auth_token", "<redacted>"), ("tenant", "t1")]);
+//! assert_eq!(mi.get_transient("auth_token"), Some("s3cr3t"));
+//! ```
+
+use crate::policy::Pattern;
+use std::fmt;
+use std::sync::RwLock;
+
+/// Shown in place of sensitive values.
+pub const REDACTED: &str = "<redacted>";
+
+static GLOBAL_SENSITIVE: RwLock<Vec<Pattern>> = RwLock::new(Vec::new());
+
+/// Marks the keys matching the pattern as sensitive for all `MetaInfo`s.
+pub fn mark_sensitive(pattern: Pattern) {
+    GLOBAL_SENSITIVE
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(pattern);
+}
+
+/// Returns whether the key is marked as sensitive globally.
+pub fn is_sensitive(key: &str) -> bool {
+    GLOBAL_SENSITIVE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .any(|p| p.matches(key))
+}
+
+/// A value that is never shown by `Debug` or `Display`.
+///
+/// Useful for typed values inserted into `MetaInfo`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Secret<T>(T);
+
+impl<T> Secret<T> {
+    #[inline]
+    pub fn new(value: T) -> Self {
+        Secret(value)
+    }
+
+    /// Returns a reference to the wrapped value.
+    #[inline]
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+
+    /// Returns the wrapped value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Secret<T> {
+    #[inline]
+    fn from(value: T) -> Self {
+        Secret(value)
+    }
+}
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl<T> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Category, ForwardWrite, MetaInfo};
+
+    #[test]
+    fn test_secret() {
+        let secret = Secret::new("token");
+        assert_eq!(format!("{:?}", secret), REDACTED);
+        assert_eq!(format!("{}", secret), REDACTED);
+        assert_eq!(*secret.expose(), "token");
+    }
+
+    #[test]
+    fn test_redacted() {
+        mark_sensitive(Pattern::exact("test_redacted_global"));
+
+        let mut mi = MetaInfo::new();
+        mi.mark_sensitive(Pattern::prefix("pwd"));
+        mi.set_persistent("pwd", "123");
+        mi.set_persistent("test_redacted_global", "456");
+        mi.set_persistent("user", "u1");
+        mi.insert_string("pwd_hint".into(), "789".into());
+
+        let debug = format!("{:?}", mi);
+        assert!(!debug.contains("123"));
+        assert!(!debug.contains("456"));
+        assert!(!debug.contains("789"));
+        assert!(debug.contains("u1"));
+
+        let (_, child) = mi.derive();
+        assert!(child.is_sensitive("pwd"));
+        assert!(child.is_sensitive("test_redacted_global"));
+        assert!(!child.is_sensitive("user"));
+        assert_eq!(
+            child
+                .redacted(Category::Persistent)
+                .find(|(k, _)| *k == "pwd"),
+            Some(("pwd", REDACTED))
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! HMAC-SHA256 signature over a designated set of persistents.
+//!
+//! The edge signs the security relevant persistents with a shared key and sends
+//! the signature as the persistent [`SIGNATURE_KEY`]; downstream services verify
+//! it before trusting these persistents.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::signing::{OnTamper, Signer, Verification};
+//! use metainfo::{ForwardRead, ForwardWrite, MetaInfo};
+//!
+//! let signer = Signer::new(b"shared secret", ["user_id", "tenant"]);
+//!
+//! let mut mi = MetaInfo::new();
+//! mi.set_persistent("user_id", "42");
+//! signer.sign(&mut mi);
+//! assert_eq!(signer.verify(&mi), Verification::Valid);
+//!
+//! mi.set_persistent("user_id", "0");
+//! assert_eq!(signer.verify_and_apply(&mut mi, OnTamper::Drop), Verification::Tampered);
+//! assert_eq!(mi.get_persistent("user_id"), None);
+//! ```
+
+use crate::{ForwardRead, ForwardWrite, MetaInfo};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::borrow::Cow;
+
+/// The persistent key which carries the signature.
+pub const SIGNATURE_KEY: &str = "metainfo-signature";
+
+const VERSION: &[u8] = b"v1";
+
+/// Result of verifying the signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    /// The signed persistents are untouched.
+    Valid,
+    /// There is no signature.
+    Missing,
+    /// The signature doesn't match the signed persistents.
+    Tampered,
+}
+
+/// What to do with signed persistents that fail the verification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnTamper {
+    /// Delete the signed persistents and the signature.
+    Drop,
+    /// Keep them, and insert the [`Verification`] into the `MetaInfo`, which
+    /// can be checked by `MetaInfo::get::<Verification>()`.
+    Flag,
+}
+
+/// Signs and verifies a designated set of persistents.
+#[derive(Clone)]
+pub struct Signer {
+    mac: Hmac<Sha256>,
+    keys: Vec<Cow<'static, str>>,
+}
+
+impl Signer {
+    /// Creates a `Signer` which covers the given persistent keys.
+    pub fn new<K, I>(secret: &[u8], keys: I) -> Self
+    where
+        K: Into<Cow<'static, str>>,
+        I: IntoIterator<Item = K>,
+    {
+        let mut keys: Vec<_> = keys.into_iter().map(Into::into).collect();
+        keys.sort();
+        keys.dedup();
+        Signer {
+            mac: Hmac::new_from_slice(secret).expect("hmac accepts keys of any length"),
+            keys,
+        }
+    }
+
+    /// Returns the covered persistent keys, sorted.
+    #[inline]
+    pub fn keys(&self) -> &[Cow<'static, str>] {
+        &self.keys
+    }
+
+    /// Computes the signature of the covered persistents and sets it as the
+    /// persistent [`SIGNATURE_KEY`].
+    pub fn sign(&self, mi: &mut MetaInfo) {
+        let signature = hex_encode(&self.compute(mi).finalize().into_bytes());
+        mi.set_persistent(SIGNATURE_KEY, signature);
+    }
+
+    /// Verifies the signature of the covered persistents.
+    pub fn verify(&self, mi: &MetaInfo) -> Verification {
+        let signature = match mi.get_persistent(SIGNATURE_KEY) {
+            Some(signature) => signature,
+            None => return Verification::Missing,
+        };
+        match hex_decode(signature) {
+            Some(signature) if self.compute(mi).verify_slice(&signature).is_ok() => {
+                Verification::Valid
+            }
+            _ => Verification::Tampered,
+        }
+    }
+
+    /// Verifies the signature and handles the persistents that fail it.
+    ///
+    /// A missing signature is also handled as tampered, since the covered
+    /// persistents can't be trusted.
+    pub fn verify_and_apply(&self, mi: &mut MetaInfo, on_tamper: OnTamper) -> Verification {
+        let verification = self.verify(mi);
+        if verification != Verification::Valid {
+            match on_tamper {
+                OnTamper::Drop => {
+                    for key in self.keys.iter() {
+                        mi.del_persistent(key);
+                    }
+                    mi.del_persistent(SIGNATURE_KEY);
+                }
+                OnTamper::Flag => mi.insert(verification),
+            }
+        }
+        verification
+    }
+
+    fn compute(&self, mi: &MetaInfo) -> Hmac<Sha256> {
+        let mut mac = self.mac.clone();
+        mac.update(VERSION);
+        for key in self.keys.iter() {
+            mac.update(&(key.len() as u32).to_be_bytes());
+            mac.update(key.as_bytes());
+            match mi.get_persistent(key) {
+                Some(value) => {
+                    mac.update(&[1]);
+                    mac.update(&(value.len() as u32).to_be_bytes());
+                    mac.update(value.as_bytes());
+                }
+                None => mac.update(&[0]),
+            }
+        }
+        mac
+    }
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        s.push(HEX[(b >> 4) as usize] as char);
+        s.push(HEX[(b & 0xf) as usize] as char);
+    }
+    s
+}
+
+fn hex_decode(s: &str) -> Option<Vec<u8>> {
+    fn nibble(c: u8) -> Option<u8> {
+        match c {
+            b'0'..=b'9' => Some(c - b'0'),
+            b'a'..=b'f' => Some(c - b'a' + 10),
+            b'A'..=b'F' => Some(c - b'A' + 10),
+            _ => None,
+        }
+    }
+    let pairs = s.as_bytes().chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return None;
+    }
+    pairs
+        .map(|pair| Some(nibble(pair[0])? << 4 | nibble(pair[1])?))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signer = Signer::new(b"secret", ["tenant", "user_id"]);
+        let mut mi = MetaInfo::new();
+        assert_eq!(signer.verify(&mi), Verification::Missing);
+
+        mi.set_persistent("tenant", "t1");
+        signer.sign(&mut mi);
+        assert_eq!(signer.verify(&mi), Verification::Valid);
+
+        // uncovered keys may change freely
+        mi.set_persistent("other", "x");
+        assert_eq!(signer.verify(&mi), Verification::Valid);
+
+        // adding a covered key is tampering too
+        mi.set_persistent("user_id", "1");
+        assert_eq!(signer.verify(&mi), Verification::Tampered);
+        mi.del_persistent("user_id");
+        assert_eq!(signer.verify(&mi), Verification::Valid);
+
+        let other = Signer::new(b"another secret", ["tenant", "user_id"]);
+        assert_eq!(other.verify(&mi), Verification::Tampered);
+
+        mi.set_persistent(SIGNATURE_KEY, "not hex");
+        assert_eq!(signer.verify(&mi), Verification::Tampered);
+    }
+
+    #[test]
+    fn test_on_tamper() {
+        let signer = Signer::new(b"secret", ["tenant"]);
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("tenant", "t1");
+        signer.sign(&mut mi);
+        mi.set_persistent("tenant", "t2");
+
+        let (mut m1, mut m2) = mi.derive();
+        assert_eq!(
+            signer.verify_and_apply(&mut m1, OnTamper::Flag),
+            Verification::Tampered
+        );
+        assert_eq!(m1.get_persistent("tenant"), Some("t2"));
+        assert_eq!(m1.get::<Verification>(), Some(&Verification::Tampered));
+
+        signer.verify_and_apply(&mut m2, OnTamper::Drop);
+        assert_eq!(m2.get_persistent("tenant"), None);
+        assert_eq!(m2.get_persistent(SIGNATURE_KEY), None);
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(hex_encode(&[0x01, 0xab, 0xff]), "01abff");
+        assert_eq!(hex_decode("01abFF"), Some(vec![0x01, 0xab, 0xff]));
+        assert_eq!(hex_decode("0"), None);
+        assert_eq!(hex_decode("zz"), None);
+    }
+}
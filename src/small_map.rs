@@ -0,0 +1,434 @@
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use fxhash::FxHashMap;
+use smallvec::SmallVec;
+use std::borrow::{Borrow, Cow};
+use std::collections::{hash_map, HashMap};
+use std::fmt;
+
+/// The number of entries stored inline, past which they are moved into a hash
+/// map.
+pub(crate) const THRESHOLD: usize = 4;
+
+/// A map from string keys, which keeps a few entries in a small vector, and
+/// switches to a hash map when it grows past [`THRESHOLD`].
+///
+/// Most requests carry a handful of keys, for which a linear scan is faster
+/// than hashing and doesn't allocate a table.
+#[derive(Clone)]
+pub(crate) struct SmallMap<V> {
+    repr: Repr<V>,
+}
+
+#[derive(Clone)]
+enum Repr<V> {
+    Inline(SmallVec<[(Cow<'static, str>, V); THRESHOLD]>),
+    Hashed(FxHashMap<Cow<'static, str>, V>),
+}
+
+impl<V> SmallMap<V> {
+    #[inline]
+    pub fn new() -> Self {
+        SmallMap {
+            repr: Repr::Inline(SmallVec::new()),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&V> {
+        match &self.repr {
+            Repr::Inline(v) => v.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            Repr::Hashed(m) => m.get(key),
+        }
+    }
+
+    #[inline]
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Inserts the k-v, returns the old value of the key.
+    pub fn insert(&mut self, key: Cow<'static, str>, value: V) -> Option<V> {
+        match &mut self.repr {
+            Repr::Inline(v) => {
+                if let Some((_, old)) = v.iter_mut().find(|(k, _)| *k == key) {
+                    return Some(std::mem::replace(old, value));
+                }
+                if v.len() < THRESHOLD {
+                    v.push((key, value));
+                    return None;
+                }
+                let mut m: FxHashMap<_, _> = std::mem::take(v).into_iter().collect();
+                m.insert(key, value);
+                self.repr = Repr::Hashed(m);
+                None
+            }
+            Repr::Hashed(m) => m.insert(key, value),
+        }
+    }
+
+    /// Removes the key, returns its value.
+    pub fn remove(&mut self, key: &str) -> Option<V> {
+        match &mut self.repr {
+            Repr::Inline(v) => {
+                let i = v.iter().position(|(k, _)| k == key)?;
+                Some(v.remove(i).1)
+            }
+            Repr::Hashed(m) => m.remove(key),
+        }
+    }
+
+    /// Returns the value of the key, inserts the default value if it's absent.
+    #[cfg(feature = "multi_value")]
+    pub fn get_or_insert_default(&mut self, key: Cow<'static, str>) -> &mut V
+    where
+        V: Default,
+    {
+        if let Repr::Inline(v) = &mut self.repr {
+            if v.len() >= THRESHOLD && !v.iter().any(|(k, _)| *k == key) {
+                self.repr = Repr::Hashed(std::mem::take(v).into_iter().collect());
+            }
+        }
+        match &mut self.repr {
+            Repr::Inline(v) => {
+                let i = match v.iter().position(|(k, _)| *k == key) {
+                    Some(i) => i,
+                    None => {
+                        v.push((key, V::default()));
+                        v.len() - 1
+                    }
+                };
+                &mut v[i].1
+            }
+            Repr::Hashed(m) => m.entry(key).or_default(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.repr = Repr::Inline(SmallVec::new());
+    }
+
+    /// Iterates the entries, in the insertion order while the map is small.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, V> {
+        match &self.repr {
+            Repr::Inline(v) => Iter(IterRepr::Inline(v.iter())),
+            Repr::Hashed(m) => Iter(IterRepr::Hashed(m.iter())),
+        }
+    }
+
+    #[inline]
+    pub fn keys(&self) -> impl Iterator<Item = &Cow<'static, str>> {
+        self.iter().map(|(k, _)| k)
+    }
+}
+
+impl<V> Default for SmallMap<V> {
+    #[inline]
+    fn default() -> Self {
+        SmallMap::new()
+    }
+}
+
+impl<V> Default for Repr<V> {
+    #[inline]
+    fn default() -> Self {
+        Repr::Inline(SmallVec::new())
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for SmallMap<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<V> Extend<(Cow<'static, str>, V)> for SmallMap<V> {
+    fn extend<I: IntoIterator<Item = (Cow<'static, str>, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<V> FromIterator<(Cow<'static, str>, V)> for SmallMap<V> {
+    fn from_iter<I: IntoIterator<Item = (Cow<'static, str>, V)>>(iter: I) -> Self {
+        let mut map = SmallMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+/// The iterator of the entries of a [`SmallMap`].
+pub(crate) struct Iter<'a, V>(IterRepr<'a, V>);
+
+enum IterRepr<'a, V> {
+    Inline(std::slice::Iter<'a, (Cow<'static, str>, V)>),
+    Hashed(hash_map::Iter<'a, Cow<'static, str>, V>),
+}
+
+impl<V> Clone for Iter<'_, V> {
+    #[inline]
+    fn clone(&self) -> Self {
+        match &self.0 {
+            IterRepr::Inline(it) => Iter(IterRepr::Inline(it.clone())),
+            IterRepr::Hashed(it) => Iter(IterRepr::Hashed(it.clone())),
+        }
+    }
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (&'a Cow<'static, str>, &'a V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            IterRepr::Inline(it) => it.next().map(|(k, v)| (k, v)),
+            IterRepr::Hashed(it) => it.next(),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            IterRepr::Inline(it) => it.size_hint(),
+            IterRepr::Hashed(it) => it.size_hint(),
+        }
+    }
+}
+
+impl<V> ExactSizeIterator for Iter<'_, V> {}
+
+impl<'a, V> IntoIterator for &'a SmallMap<V> {
+    type Item = (&'a Cow<'static, str>, &'a V);
+    type IntoIter = Iter<'a, V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// The owning iterator of the entries of a [`SmallMap`].
+pub(crate) struct IntoIter<V>(IntoIterRepr<V>);
+
+enum IntoIterRepr<V> {
+    Inline(smallvec::IntoIter<[(Cow<'static, str>, V); THRESHOLD]>),
+    Hashed(hash_map::IntoIter<Cow<'static, str>, V>),
+}
+
+impl<V> Iterator for IntoIter<V> {
+    type Item = (Cow<'static, str>, V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            IntoIterRepr::Inline(it) => it.next(),
+            IntoIterRepr::Hashed(it) => it.next(),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            IntoIterRepr::Inline(it) => it.size_hint(),
+            IntoIterRepr::Hashed(it) => it.size_hint(),
+        }
+    }
+}
+
+impl<V> IntoIterator for SmallMap<V> {
+    type Item = (Cow<'static, str>, V);
+    type IntoIter = IntoIter<V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        match self.repr {
+            Repr::Inline(v) => IntoIter(IntoIterRepr::Inline(v.into_iter())),
+            Repr::Hashed(m) => IntoIter(IntoIterRepr::Hashed(m.into_iter())),
+        }
+    }
+}
+
+enum EntriesRepr<'a, V, T: ?Sized> {
+    Empty,
+    Small(Iter<'a, V>),
+    Collected(std::vec::IntoIter<(&'a str, &'a T)>),
+}
+
+impl<V, T: ?Sized> Clone for EntriesRepr<'_, V, T> {
+    fn clone(&self) -> Self {
+        match self {
+            EntriesRepr::Empty => EntriesRepr::Empty,
+            EntriesRepr::Small(it) => EntriesRepr::Small(it.clone()),
+            EntriesRepr::Collected(it) => EntriesRepr::Collected(it.clone()),
+        }
+    }
+}
+
+impl<'a, V: Borrow<T>, T: ?Sized> EntriesRepr<'a, V, T> {
+    #[inline]
+    fn new(map: Option<&'a SmallMap<V>>) -> Self {
+        match map {
+            Some(map) => EntriesRepr::Small(map.iter()),
+            None => EntriesRepr::Empty,
+        }
+    }
+
+    #[inline]
+    fn next(&mut self) -> Option<(&'a str, &'a T)> {
+        match self {
+            EntriesRepr::Empty => None,
+            EntriesRepr::Small(it) => it.next().map(|(k, v)| (k.as_ref(), v.borrow())),
+            EntriesRepr::Collected(it) => it.next(),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            EntriesRepr::Empty => (0, Some(0)),
+            EntriesRepr::Small(it) => it.size_hint(),
+            EntriesRepr::Collected(it) => it.size_hint(),
+        }
+    }
+}
+
+macro_rules! entries_impl {
+    ($name:ident, $storage:ty, $value:ty, $owned:ty, $to_owned:expr) => {
+        impl<'a> $name<'a> {
+            #[inline]
+            pub(crate) fn new(map: Option<&'a SmallMap<$storage>>) -> Self {
+                $name(EntriesRepr::new(map))
+            }
+
+            /// Returns `true` if there are no entries left.
+            #[inline]
+            pub fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// Collects the entries into a std map, `None` if there are none.
+            pub(crate) fn into_hash_map(self) -> Option<HashMap<Cow<'static, str>, $owned>> {
+                if self.is_empty() {
+                    return None;
+                }
+                Some(
+                    self.map(|(k, v)| (Cow::Owned(k.to_owned()), $to_owned(v)))
+                        .collect(),
+                )
+            }
+        }
+
+        impl Default for $name<'_> {
+            #[inline]
+            fn default() -> Self {
+                $name(EntriesRepr::Empty)
+            }
+        }
+
+        impl<'a> Iterator for $name<'a> {
+            type Item = (&'a str, &'a $value);
+
+            #[inline]
+            fn next(&mut self) -> Option<Self::Item> {
+                self.0.next()
+            }
+
+            #[inline]
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.0.size_hint()
+            }
+        }
+
+        impl ExactSizeIterator for $name<'_> {}
+
+        impl<'a> FromIterator<(&'a str, &'a $value)> for $name<'a> {
+            fn from_iter<I: IntoIterator<Item = (&'a str, &'a $value)>>(iter: I) -> Self {
+                let entries: Vec<_> = iter.into_iter().collect();
+                $name(EntriesRepr::Collected(entries.into_iter()))
+            }
+        }
+    };
+}
+
+/// The string k-v of a category, see
+/// [`ForwardRead::persistents`](crate::ForwardRead::persistents) and the like.
+///
+/// Other implementations of the traits can collect their k-v into it.
+#[derive(Clone)]
+pub struct Entries<'a>(EntriesRepr<'a, Cow<'static, str>, str>);
+
+entries_impl!(
+    Entries,
+    Cow<'static, str>,
+    str,
+    Cow<'static, str>,
+    |v: &str| Cow::Owned(v.to_owned())
+);
+
+/// The bytes k-v of a category, see
+/// [`ForwardRead::persistent_bytes`](crate::ForwardRead::persistent_bytes) and
+/// the like.
+#[cfg(feature = "bytes")]
+#[derive(Clone)]
+pub struct BytesEntries<'a>(EntriesRepr<'a, Bytes, Bytes>);
+
+#[cfg(feature = "bytes")]
+entries_impl!(BytesEntries, Bytes, Bytes, Bytes, Bytes::clone);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_map() {
+        let mut map = SmallMap::new();
+        for i in 0..THRESHOLD {
+            assert_eq!(map.insert(i.to_string().into(), i), None);
+        }
+        assert!(matches!(map.repr, Repr::Inline(_)));
+        assert_eq!(map.insert("0".into(), 10), Some(0));
+        assert_eq!(
+            map.keys().map(|k| k.as_ref()).collect::<Vec<_>>(),
+            ["0", "1", "2", "3"]
+        );
+
+        map.insert("4".into(), 4);
+        assert!(matches!(map.repr, Repr::Hashed(_)));
+        assert_eq!(map.iter().len(), THRESHOLD + 1);
+        assert_eq!(map.get("0"), Some(&10));
+        assert_eq!(map.remove("0"), Some(10));
+        assert_eq!(map.get("0"), None);
+        assert_eq!(map.iter().len(), THRESHOLD);
+
+        let copy: SmallMap<_> = map.clone().into_iter().collect();
+        assert_eq!(copy.iter().len(), THRESHOLD);
+        map.clear();
+        assert_eq!(map.iter().len(), 0);
+    }
+
+    #[test]
+    fn test_entries() {
+        let map: SmallMap<Cow<'static, str>> = [("k".into(), "v".into())].into_iter().collect();
+        let entries = Entries::new(Some(&map));
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries.clone().next(), Some(("k", "v")));
+
+        // other implementations collect their k-v
+        let entries: Entries<'_> = [("a", "1"), ("b", "2")].into_iter().collect();
+        assert_eq!(entries.len(), 2);
+        assert!(Entries::default().is_empty());
+    }
+
+    #[cfg(feature = "multi_value")]
+    #[test]
+    fn test_get_or_insert_default() {
+        let mut map: SmallMap<Vec<u8>> = SmallMap::new();
+        for i in 0..=THRESHOLD as u8 {
+            map.get_or_insert_default(i.to_string().into()).push(i);
+            map.get_or_insert_default(i.to_string().into()).push(i);
+        }
+        assert!(matches!(map.repr, Repr::Hashed(_)));
+        assert_eq!(map.get("3"), Some(&vec![3, 3]));
+    }
+}
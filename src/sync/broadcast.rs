@@ -0,0 +1,107 @@
+//! Wrappers of `tokio::sync::broadcast` carrying `MetaInfo`.
+//!
+//! Every receiver gets its own child of the `MetaInfo` captured by `send`.
+
+use super::{capture, Envelope};
+use crate::MetaInfo;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+pub use tokio::sync::broadcast::error::{RecvError, SendError, TryRecvError};
+
+/// Creates a broadcast channel, see `tokio::sync::broadcast::channel`.
+pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let (tx, rx) = broadcast::channel(capacity);
+    (Sender { inner: tx }, Receiver { inner: rx })
+}
+
+#[derive(Debug, Clone)]
+struct Message<T> {
+    value: T,
+    metainfo: Arc<MetaInfo>,
+}
+
+impl<T> From<Message<T>> for Envelope<T> {
+    #[inline]
+    fn from(message: Message<T>) -> Self {
+        Envelope::new(message.value, MetaInfo::from(message.metainfo))
+    }
+}
+
+#[derive(Debug)]
+pub struct Sender<T> {
+    inner: broadcast::Sender<Message<T>>,
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Sender {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: Clone> Sender<T> {
+    /// Sends the value with the current `MetaInfo`, returns the number of
+    /// receivers which will see it.
+    pub fn send(&self, value: T) -> Result<usize, SendError<T>> {
+        let message = Message {
+            value,
+            metainfo: Arc::new(capture()),
+        };
+        self.inner.send(message).map_err(|e| SendError(e.0.value))
+    }
+
+    #[inline]
+    pub fn subscribe(&self) -> Receiver<T> {
+        Receiver {
+            inner: self.inner.subscribe(),
+        }
+    }
+
+    #[inline]
+    pub fn receiver_count(&self) -> usize {
+        self.inner.receiver_count()
+    }
+}
+
+#[derive(Debug)]
+pub struct Receiver<T> {
+    inner: broadcast::Receiver<Message<T>>,
+}
+
+impl<T: Clone> Receiver<T> {
+    #[inline]
+    pub async fn recv(&mut self) -> Result<Envelope<T>, RecvError> {
+        self.inner.recv().await.map(Into::into)
+    }
+
+    #[inline]
+    pub fn try_recv(&mut self) -> Result<Envelope<T>, TryRecvError> {
+        self.inner.try_recv().map(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ForwardRead, ForwardWrite, METAINFO};
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_broadcast() {
+        let (tx, mut rx1) = channel(4);
+        let mut rx2 = tx.subscribe();
+
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("k", "v");
+        METAINFO.sync_scope(RefCell::new(mi), || tx.send(1).unwrap());
+
+        for rx in [&mut rx1, &mut rx2] {
+            let (value, mut mi) = rx.try_recv().unwrap().into_parts();
+            assert_eq!(value, 1);
+            assert_eq!(mi.get_persistent("k"), Some("v"));
+            mi.set_persistent("k", "changed");
+        }
+        assert!(matches!(rx1.try_recv(), Err(TryRecvError::Empty)));
+    }
+}
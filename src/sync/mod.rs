@@ -0,0 +1,92 @@
+//! Channels which carry the current `MetaInfo` along with each message.
+//!
+//! `send` captures a snapshot of the current `MetaInfo` (see
+//! [`crate::current`]) together with the value, and `recv` returns an
+//! [`Envelope`], whose [`scope`](Envelope::scope) runs the processing with the
+//! captured `MetaInfo` as the task-local [`METAINFO`].
+//!
+//! The current `MetaInfo` is only read by `send`, which leaves it as is. The
+//! snapshot has its k-v and the typed values of its parents, but not the typed
+//! values inserted in its own scope, which can't be copied.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::{ForwardRead, ForwardWrite, MetaInfo, METAINFO};
+//! use std::cell::RefCell;
+//!
+//! # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+//! let (tx, mut rx) = metainfo::sync::mpsc::channel(8);
+//!
+//! let mut mi = MetaInfo::new();
+//! mi.set_persistent("tenant", "t1");
+//! METAINFO.scope(RefCell::new(mi), async { tx.send("job").await.unwrap() }).await;
+//!
+//! let envelope = rx.recv().await.unwrap();
+//! envelope
+//!     .scope(|job| async move {
+//!         assert_eq!(job, "job");
+//!         METAINFO.with(|mi| assert_eq!(mi.borrow().get_persistent("tenant"), Some("t1")));
+//!     })
+//!     .await;
+//! # });
+//! ```
+
+use crate::{MetaInfo, METAINFO};
+use std::cell::RefCell;
+use std::future::Future;
+
+pub mod broadcast;
+pub mod mpsc;
+pub mod oneshot;
+
+/// A received value together with the `MetaInfo` captured when it was sent.
+#[derive(Debug)]
+pub struct Envelope<T> {
+    value: T,
+    metainfo: MetaInfo,
+}
+
+impl<T> Envelope<T> {
+    #[inline]
+    pub fn new(value: T, metainfo: MetaInfo) -> Self {
+        Envelope { value, metainfo }
+    }
+
+    #[inline]
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    #[inline]
+    pub fn metainfo(&self) -> &MetaInfo {
+        &self.metainfo
+    }
+
+    #[inline]
+    pub fn into_parts(self) -> (T, MetaInfo) {
+        (self.value, self.metainfo)
+    }
+
+    /// Runs the future returned by `f` with the captured `MetaInfo` as [`METAINFO`].
+    pub async fn scope<F, Fut>(self, f: F) -> Fut::Output
+    where
+        F: FnOnce(T) -> Fut,
+        Fut: Future,
+    {
+        METAINFO
+            .scope(RefCell::new(self.metainfo), f(self.value))
+            .await
+    }
+
+    /// Runs `f` with the captured `MetaInfo` as [`METAINFO`].
+    pub fn sync_scope<R, F: FnOnce(T) -> R>(self, f: F) -> R {
+        let value = self.value;
+        METAINFO.sync_scope(RefCell::new(self.metainfo), || f(value))
+    }
+}
+
+/// Captures a snapshot of the current `MetaInfo`, or an empty one.
+#[inline]
+fn capture() -> MetaInfo {
+    crate::current::with_current(|mi| mi.map(MetaInfo::snapshot)).unwrap_or_default()
+}
@@ -0,0 +1,173 @@
+//! Wrappers of `tokio::sync::mpsc` carrying `MetaInfo`.
+
+use super::{capture, Envelope};
+use tokio::sync::mpsc;
+pub use tokio::sync::mpsc::error::{SendError, TryRecvError, TrySendError};
+
+/// Creates a bounded channel, see `tokio::sync::mpsc::channel`.
+pub fn channel<T>(buffer: usize) -> (Sender<T>, Receiver<T>) {
+    let (tx, rx) = mpsc::channel(buffer);
+    (Sender { inner: tx }, Receiver { inner: rx })
+}
+
+/// Creates an unbounded channel, see `tokio::sync::mpsc::unbounded_channel`.
+pub fn unbounded_channel<T>() -> (UnboundedSender<T>, UnboundedReceiver<T>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    (
+        UnboundedSender { inner: tx },
+        UnboundedReceiver { inner: rx },
+    )
+}
+
+#[derive(Debug)]
+pub struct Sender<T> {
+    inner: mpsc::Sender<Envelope<T>>,
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Sender {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    /// Sends the value with the current `MetaInfo`, waiting for capacity.
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.inner
+            .send(Envelope::new(value, capture()))
+            .await
+            .map_err(|e| SendError(e.0.value))
+    }
+
+    /// Tries to send the value with the current `MetaInfo` immediately.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.inner
+            .try_send(Envelope::new(value, capture()))
+            .map_err(|e| match e {
+                TrySendError::Full(e) => TrySendError::Full(e.value),
+                TrySendError::Closed(e) => TrySendError::Closed(e.value),
+            })
+    }
+
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+#[derive(Debug)]
+pub struct Receiver<T> {
+    inner: mpsc::Receiver<Envelope<T>>,
+}
+
+impl<T> Receiver<T> {
+    #[inline]
+    pub async fn recv(&mut self) -> Option<Envelope<T>> {
+        self.inner.recv().await
+    }
+
+    #[inline]
+    pub fn try_recv(&mut self) -> Result<Envelope<T>, TryRecvError> {
+        self.inner.try_recv()
+    }
+
+    #[inline]
+    pub fn close(&mut self) {
+        self.inner.close()
+    }
+}
+
+#[derive(Debug)]
+pub struct UnboundedSender<T> {
+    inner: mpsc::UnboundedSender<Envelope<T>>,
+}
+
+impl<T> Clone for UnboundedSender<T> {
+    fn clone(&self) -> Self {
+        UnboundedSender {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> UnboundedSender<T> {
+    /// Sends the value with the current `MetaInfo`.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.inner
+            .send(Envelope::new(value, capture()))
+            .map_err(|e| SendError(e.0.value))
+    }
+
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+#[derive(Debug)]
+pub struct UnboundedReceiver<T> {
+    inner: mpsc::UnboundedReceiver<Envelope<T>>,
+}
+
+impl<T> UnboundedReceiver<T> {
+    #[inline]
+    pub async fn recv(&mut self) -> Option<Envelope<T>> {
+        self.inner.recv().await
+    }
+
+    #[inline]
+    pub fn try_recv(&mut self) -> Result<Envelope<T>, TryRecvError> {
+        self.inner.try_recv()
+    }
+
+    #[inline]
+    pub fn close(&mut self) {
+        self.inner.close()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ForwardRead, ForwardWrite, MetaInfo, METAINFO};
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_mpsc() {
+        let (tx, mut rx) = unbounded_channel();
+        tx.send(0).unwrap();
+
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("k", "v");
+        mi.insert_string("s".into(), "v".into());
+        METAINFO.sync_scope(RefCell::new(mi), || {
+            // inside a shared borrow
+            crate::current::with_current(|_| tx.send(1).unwrap());
+            // the sender keeps its own MetaInfo, with the string k-v in its scope
+            METAINFO.with(|mi| {
+                let mut mi = mi.borrow_mut();
+                assert_eq!(mi.get_persistent("k"), Some("v"));
+                assert_eq!(mi.remove_string("s").as_deref(), Some("v"));
+            });
+        });
+
+        let (value, mi) = rx.try_recv().unwrap().into_parts();
+        assert_eq!(value, 0);
+        assert_eq!(mi.get_persistent("k"), None);
+
+        let value = rx.try_recv().unwrap().sync_scope(|value| {
+            METAINFO.with(|mi| {
+                let mi = mi.borrow();
+                assert_eq!(mi.get_persistent("k"), Some("v"));
+                assert_eq!(mi.get_string("s").map(|s| s.as_ref()), Some("v"));
+            });
+            value
+        });
+        assert_eq!(value, 1);
+
+        drop(rx);
+        assert_eq!(tx.send(2), Err(SendError(2)));
+    }
+}
@@ -0,0 +1,80 @@
+//! Wrappers of `tokio::sync::oneshot` carrying `MetaInfo`.
+
+use super::{capture, Envelope};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::oneshot;
+pub use tokio::sync::oneshot::error::{RecvError, TryRecvError};
+
+/// Creates a oneshot channel, see `tokio::sync::oneshot::channel`.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let (tx, rx) = oneshot::channel();
+    (Sender { inner: tx }, Receiver { inner: rx })
+}
+
+#[derive(Debug)]
+pub struct Sender<T> {
+    inner: oneshot::Sender<Envelope<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Sends the value with the current `MetaInfo`, returns the value back if
+    /// the receiver is dropped.
+    pub fn send(self, value: T) -> Result<(), T> {
+        self.inner
+            .send(Envelope::new(value, capture()))
+            .map_err(|e| e.value)
+    }
+
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+/// Resolves to the sent [`Envelope`].
+#[derive(Debug)]
+pub struct Receiver<T> {
+    inner: oneshot::Receiver<Envelope<T>>,
+}
+
+impl<T> Receiver<T> {
+    #[inline]
+    pub fn try_recv(&mut self) -> Result<Envelope<T>, TryRecvError> {
+        self.inner.try_recv()
+    }
+
+    #[inline]
+    pub fn close(&mut self) {
+        self.inner.close()
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<Envelope<T>, RecvError>;
+
+    #[inline]
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.inner).poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ForwardRead, ForwardWrite, MetaInfo, METAINFO};
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_oneshot() {
+        let (tx, mut rx) = channel();
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("k", "v");
+        METAINFO.sync_scope(RefCell::new(mi), || tx.send(1).unwrap());
+
+        let (value, mi) = rx.try_recv().unwrap().into_parts();
+        assert_eq!(value, 1);
+        assert_eq!(mi.get_persistent("k"), Some("v"));
+    }
+}
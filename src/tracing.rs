@@ -0,0 +1,400 @@
+//! Integration with the `tracing` crate.
+//!
+//! [`record`] copies selected metainfo into the fields of a span, and
+//! [`MetaInfoLayer`] captures selected metainfo of the current `MetaInfo`, see
+//! [`current`](crate::current), for every span and event. Added after a `tracing_subscriber::fmt` layer, the
+//! layer adds the captured metainfo to the fields it prints for the spans.
+//!
+//! Examples:
+//! ```rust
+//! use metainfo::tracing::{record, Field};
+//! use metainfo::{ForwardWrite, MetaInfo};
+//!
+//! let mut mi = MetaInfo::new();
+//! mi.set_persistent("tenant", "t1");
+//!
+//! let span = tracing::info_span!("handle", tenant = tracing::field::Empty);
+//! record(&span, &mi, &[Field::persistent("tenant")]);
+//! ```
+
+pub use crate::Field;
+use crate::{current, MetaInfo};
+use ::tracing::{span, Event, Span, Subscriber};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt;
+use tracing_subscriber::fmt::format::DefaultFields;
+use tracing_subscriber::fmt::FormattedFields;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Records the selected metainfo as fields of the span.
+///
+/// As required by `tracing`, the fields must be declared when the span is
+/// created, e.g. as `tracing::field::Empty`; undeclared ones are ignored.
+pub fn record(span: &Span, mi: &MetaInfo, fields: &[Field]) {
+    for field in fields {
+        if let Some(value) = field.value(mi) {
+            span.record(field.name(), value);
+        }
+    }
+}
+
+/// Records the selected metainfo of the current `MetaInfo` as fields of the
+/// current span.
+///
+/// Nothing is recorded if there is no current `MetaInfo`, or it's mutably
+/// borrowed.
+pub fn record_current(fields: &[Field]) {
+    current::with_current(|mi| {
+        if let Some(mi) = mi {
+            record(&Span::current(), mi, fields)
+        }
+    })
+}
+
+/// The metainfo captured by [`MetaInfoLayer`].
+///
+/// Formatters and other layers can read that of a span by
+/// `span.extensions().get::<CapturedFields>()`, and that of an event by
+/// [`event_fields`]. Events have no extensions of their own.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapturedFields {
+    fields: Vec<(Cow<'static, str>, String)>,
+}
+
+impl CapturedFields {
+    /// Iterates the captured field names and values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields.iter().map(|(k, v)| (k.as_ref(), v.as_str()))
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+impl fmt::Display for CapturedFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (k, v)) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}={}", k, v)?;
+        }
+        Ok(())
+    }
+}
+
+// the addresses of the event and its callsite metadata; that of the event is
+// only valid while it's dispatched, and may be reused by the next one
+type EventId = (usize, usize);
+
+#[inline]
+fn event_id(event: &Event<'_>) -> EventId {
+    (
+        event as *const Event<'_> as usize,
+        event.metadata() as *const _ as usize,
+    )
+}
+
+thread_local! {
+    // captured by `MetaInfoLayer` for the event being dispatched
+    static EVENT_FIELDS: RefCell<Option<(EventId, CapturedFields)>> = const { RefCell::new(None) };
+}
+
+/// Returns the metainfo captured for the event being dispatched.
+///
+/// [`MetaInfoLayer`] captures it when the event fires, so the layers after it
+/// can read it in their `on_event`. Returns `None` if nothing was captured for
+/// this event, e.g. when a filter skipped `MetaInfoLayer`.
+pub fn event_fields(event: &Event<'_>) -> Option<CapturedFields> {
+    let id = event_id(event);
+    EVENT_FIELDS.with(|fields| {
+        let mut fields = fields.borrow_mut();
+        match &*fields {
+            Some((captured, fields)) if *captured == id => Some(fields.clone()),
+            _ => {
+                // captured for a previous event
+                *fields = None;
+                None
+            }
+        }
+    })
+}
+
+/// A `tracing_subscriber::Layer` that captures the configured keys of the
+/// current `MetaInfo` into [`CapturedFields`].
+///
+/// The fields of a span are captured when it's created, and stored in its
+/// extensions. They are also appended to the fields formatted by a
+/// `tracing_subscriber::fmt` layer with the default field formatter, if it's
+/// added before this one, so the events in the span are printed with them.
+///
+/// The fields of an event are captured when it fires, and can be read by
+/// [`event_fields`]; the layers reading them must be added after this one.
+#[derive(Debug, Clone, Default)]
+pub struct MetaInfoLayer {
+    fields: Vec<Field>,
+}
+
+impl MetaInfoLayer {
+    pub fn new(fields: Vec<Field>) -> Self {
+        MetaInfoLayer { fields }
+    }
+
+    fn capture(&self) -> Option<CapturedFields> {
+        // the metainfo may be mutably borrowed by the code which emits
+        current::with_current(|mi| {
+            let mi = mi?;
+            let fields = self
+                .fields
+                .iter()
+                .filter_map(|field| {
+                    field
+                        .value(mi)
+                        .map(|value| (field.name.clone(), value.to_owned()))
+                })
+                .collect();
+            Some(CapturedFields { fields })
+        })
+        .filter(|fields| !fields.is_empty())
+    }
+}
+
+impl<S> Layer<S> for MetaInfoLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if let (Some(span), Some(fields)) = (ctx.span(id), self.capture()) {
+            let mut extensions = span.extensions_mut();
+            if let Some(formatted) = extensions.get_mut::<FormattedFields<DefaultFields>>() {
+                let mut out = std::mem::take(&mut formatted.fields);
+                for (k, v) in fields.iter() {
+                    if !out.is_empty() {
+                        out.push(' ');
+                    }
+                    // as `DefaultFields` formats a `&str`
+                    out.push_str(&format!("{}={:?}", k, v));
+                }
+                formatted.fields = out;
+            }
+            extensions.insert(fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let captured = self.capture().map(|fields| (event_id(event), fields));
+        EVENT_FIELDS.with(|fields| *fields.borrow_mut() = captured);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ForwardWrite, METAINFO};
+    use std::cell::RefCell;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::filter::filter_fn;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Registry;
+
+    /// Collects the recorded values and the captured `CapturedFields`.
+    #[derive(Clone, Default)]
+    struct Collector(Arc<Mutex<Vec<String>>>);
+
+    impl<S> Layer<S> for Collector
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_record(&self, _id: &span::Id, values: &span::Record<'_>, _ctx: Context<'_, S>) {
+            let mut out = String::new();
+            values.record(
+                &mut |field: &::tracing::field::Field, value: &dyn fmt::Debug| {
+                    out = format!("{}={:?}", field.name(), value);
+                },
+            );
+            self.0.lock().unwrap().push(out);
+        }
+
+        fn on_new_span(&self, _attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+            let span = ctx.span(id).unwrap();
+            let extensions = span.extensions();
+            if let Some(fields) = extensions.get::<CapturedFields>() {
+                self.0.lock().unwrap().push(format!("span {}", fields));
+            }
+        }
+
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            let fields = event_fields(event).map(|fields| fields.to_string());
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("event {}", fields.unwrap_or_default()));
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let collector = Collector::default();
+        let subscriber = Registry::default().with(collector.clone());
+
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("tenant", "t1");
+        mi.insert_string("user".into(), "u1".into());
+        ::tracing::subscriber::with_default(subscriber, || {
+            let span = ::tracing::info_span!(
+                "test",
+                tenant = ::tracing::field::Empty,
+                user_id = ::tracing::field::Empty
+            );
+            record(
+                &span,
+                &mi,
+                &[
+                    Field::persistent("tenant"),
+                    Field::string("user").rename("user_id"),
+                    Field::transient("missing"),
+                ],
+            );
+        });
+        assert_eq!(
+            *collector.0.lock().unwrap(),
+            ["tenant=\"t1\"", "user_id=\"u1\""]
+        );
+    }
+
+    #[test]
+    fn test_layer() {
+        let collector = Collector::default();
+        let subscriber = Registry::default()
+            .with(MetaInfoLayer::new(vec![
+                Field::persistent("tenant"),
+                Field::transient("token"),
+            ]))
+            .with(collector.clone());
+
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("tenant", "t1");
+        mi.set_transient("token", "secret");
+        mi.mark_sensitive(crate::policy::Pattern::exact("token"));
+        ::tracing::subscriber::with_default(subscriber, || {
+            // created before the scope
+            let outer = ::tracing::info_span!("outer");
+            METAINFO.sync_scope(RefCell::new(mi), || {
+                ::tracing::info!("no span");
+                let _enter = outer.enter();
+                ::tracing::info!("outer span");
+                let _enter = ::tracing::info_span!("inner").entered();
+                METAINFO.with(|mi| {
+                    let mut mi = mi.borrow_mut();
+                    mi.set_persistent("tenant", "t2");
+                    ::tracing::info!("borrowed");
+                });
+                ::tracing::info!("changed");
+            });
+            ::tracing::info!("out of scope");
+        });
+        assert_eq!(
+            *collector.0.lock().unwrap(),
+            [
+                "event tenant=t1 token=<redacted>",
+                "event tenant=t1 token=<redacted>",
+                "span tenant=t1 token=<redacted>",
+                "event ",
+                "event tenant=t2 token=<redacted>",
+                "event ",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_layer_filtered() {
+        let collector = Collector::default();
+        let layer = MetaInfoLayer::new(vec![Field::persistent("tenant")])
+            .with_filter(filter_fn(|metadata| metadata.target() != "skipped"));
+        let subscriber = Registry::default().with(layer).with(collector.clone());
+
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("tenant", "t1");
+        ::tracing::subscriber::with_default(subscriber, || {
+            METAINFO.sync_scope(RefCell::new(mi), || {
+                ::tracing::info!("captured");
+                ::tracing::info!(target: "skipped", "not captured");
+            });
+        });
+        assert_eq!(*collector.0.lock().unwrap(), ["event tenant=t1", "event "]);
+    }
+
+    #[test]
+    fn test_current() {
+        let collector = Collector::default();
+        let subscriber = Registry::default()
+            .with(MetaInfoLayer::new(vec![Field::persistent("tenant")]))
+            .with(collector.clone());
+
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("tenant", "t1");
+        ::tracing::subscriber::with_default(subscriber, || {
+            // e.g. in a blocking closure
+            let guard = current::set_current(mi);
+            let _enter =
+                ::tracing::info_span!("handle", tenant = ::tracing::field::Empty).entered();
+            record_current(&[Field::persistent("tenant")]);
+            ::tracing::info!("captured");
+            drop(guard);
+            record_current(&[Field::persistent("tenant")]);
+            ::tracing::info!("not captured");
+        });
+        assert_eq!(
+            *collector.0.lock().unwrap(),
+            [
+                "span tenant=t1",
+                "tenant=\"t1\"",
+                "event tenant=t1",
+                "event "
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fmt_layer() {
+        #[derive(Clone, Default)]
+        struct Output(Arc<Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Output {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Output::default();
+        let writer = output.clone();
+        let subscriber = Registry::default()
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .with_ansi(false)
+                    .without_time()
+                    .with_writer(move || writer.clone()),
+            )
+            .with(MetaInfoLayer::new(vec![Field::persistent("tenant")]));
+
+        let mut mi = MetaInfo::new();
+        mi.set_persistent("tenant", "t1");
+        ::tracing::subscriber::with_default(subscriber, || {
+            METAINFO.sync_scope(RefCell::new(mi), || {
+                let _enter = ::tracing::info_span!("handle", id = 1).entered();
+                ::tracing::info!("hello");
+            });
+        });
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("handle{id=1 tenant=\"t1\"}"), "{}", output);
+    }
+}
@@ -0,0 +1,132 @@
+//! Values which are either text or bytes.
+//!
+//! With the `bytes` feature, each category of the forward and backward metainfo
+//! can also hold `Bytes` values, set by `set_*_bytes`. A key holds either a text
+//! or a bytes value, setting one replaces the other.
+//!
+//! Examples:
+//! ```rust
+//! use bytes::Bytes;
+//! use metainfo::{Category, ForwardRead, ForwardWrite, MetaInfo, Value};
+//!
+//! let mut mi = MetaInfo::new();
+//! mi.set_persistent_bytes("auth", Bytes::from_static(b"\x08\x01"));
+//! assert_eq!(mi.get_persistent("auth"), None);
+//! assert_eq!(
+//!     mi.get_value_by_category(Category::Persistent, "auth"),
+//!     Some(Value::Bytes(&Bytes::from_static(b"\x08\x01")))
+//! );
+//! ```
+
+use crate::kv::BytesEntries;
+use crate::{BackwardRead, Category, ForwardRead, MetaInfo};
+use bytes::Bytes;
+
+/// A text or bytes value of metainfo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value<'a> {
+    Text(&'a str),
+    Bytes(&'a Bytes),
+}
+
+impl<'a> Value<'a> {
+    /// Returns the text, or `None` if it's a bytes value.
+    #[inline]
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self {
+            Value::Text(text) => Some(text),
+            Value::Bytes(_) => None,
+        }
+    }
+
+    /// Returns the raw bytes of either kind of value.
+    #[inline]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        match self {
+            Value::Text(text) => text.as_bytes(),
+            Value::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+impl MetaInfo {
+    /// Get the text or bytes value of the key in the given category.
+    ///
+    /// The string category only holds text values.
+    pub fn get_value_by_category<K: AsRef<str>>(
+        &self,
+        category: Category,
+        key: K,
+    ) -> Option<Value<'_>> {
+        let key = key.as_ref();
+        if let Some(text) = self.get_by_category(category, key) {
+            return Some(Value::Text(text));
+        }
+        match category {
+            Category::Persistent => self.get_persistent_bytes(key),
+            Category::Transient => self.get_transient_bytes(key),
+            Category::Upstream => self.get_upstream_bytes(key),
+            Category::BackwardTransient => self.get_backward_transient_bytes(key),
+            Category::BackwardDownstream => self.get_backward_downstream_bytes(key),
+            Category::String => None,
+        }
+        .map(Value::Bytes)
+    }
+
+    /// Returns the bytes k-v of the given category in the current scope.
+    pub(crate) fn bytes_entries(&self, category: Category) -> BytesEntries<'_> {
+        match category {
+            Category::Persistent => self.persistent_bytes(),
+            Category::Transient => self.transient_bytes(),
+            Category::Upstream => self.upstream_bytes(),
+            Category::BackwardTransient => self.backward_transient_bytes(),
+            Category::BackwardDownstream => self.backward_downstream_bytes(),
+            Category::String => BytesEntries::default(),
+        }
+    }
+
+    /// Returns the bytes k-v of the given category which the outbound policy
+    /// allows to send.
+    pub(crate) fn outbound_bytes_entries(&self, category: Category) -> BytesEntries<'_> {
+        if self.outbound_policy.is_none() {
+            return self.bytes_entries(category);
+        }
+        self.bytes_entries(category)
+            .filter(|(k, _)| self.outbound_allows(category, k))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BackwardWrite, ForwardWrite};
+
+    #[test]
+    fn test_bytes_values() {
+        let mut mi = MetaInfo::new();
+        mi.set_transient("k", "text");
+        mi.set_backward_transient_bytes("k", vec![1, 2]);
+        assert_eq!(
+            mi.get_value_by_category(Category::Transient, "k"),
+            Some(Value::Text("text"))
+        );
+        let value = mi
+            .get_value_by_category(Category::BackwardTransient, "k")
+            .unwrap();
+        assert_eq!(value.as_str(), None);
+        assert_eq!(value.as_bytes(), [1, 2]);
+
+        // the bytes values are passed to the children
+        let (mut mi, child) = mi.derive();
+        assert_eq!(child.get_backward_transient_bytes("k").unwrap()[..], [1, 2]);
+        assert_eq!(
+            child.backward_transient_bytes().collect::<Vec<_>>(),
+            [("k", &Bytes::from_static(&[1, 2]))]
+        );
+
+        mi.del_backward_transient_bytes("k");
+        assert!(mi.backward_transient_bytes().is_empty());
+        assert_eq!(mi.get_value_by_category(Category::String, "k"), None);
+    }
+}